@@ -0,0 +1,164 @@
+#[macro_use]
+extern crate criterion;
+extern crate ecoji;
+
+use std::cmp;
+use std::io::{self, Read};
+
+use criterion::{Criterion, Throughput};
+
+fn input(len: usize) -> Vec<u8> {
+    // Deterministic pseudo-random data, so every emoji of the alphabet is likely to be used
+    let mut state: u32 = 0x1234_5678;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+/// A reader which returns at most three bytes per call, so filling a five-byte group always
+/// takes several reads.
+struct SmallReads<'a>(&'a [u8]);
+
+impl<'a> Read for SmallReads<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = cmp::min(buf.len(), 3);
+        self.0.read(&mut buf[..n])
+    }
+}
+
+/// The original `read_exact` of the encoder, which reborrows the rest of the buffer after every
+/// read; kept as the baseline for the index-based version below.
+fn read_exact_reslicing<R: Read>(source: &mut R, mut buf: &mut [u8]) -> io::Result<usize> {
+    let mut bytes_read = 0;
+    while !buf.is_empty() {
+        match source.read(buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                let tmp = buf;
+                buf = &mut tmp[n..];
+                bytes_read += n;
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(bytes_read)
+}
+
+/// The current `read_exact` of the encoder, which keeps an index into a fixed-size group buffer.
+fn read_exact_indexed<R: Read>(source: &mut R, buf: &mut [u8; 5]) -> io::Result<usize> {
+    let mut bytes_read = 0;
+    while bytes_read < buf.len() {
+        match source.read(&mut buf[bytes_read..]) {
+            Ok(0) => break,
+            Ok(n) => bytes_read += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(bytes_read)
+}
+
+fn bench_read_exact(c: &mut Criterion) {
+    let data = input(64 * 1024);
+
+    let mut group = c.benchmark_group("read_exact");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function("reslicing (baseline), small reads", |b| {
+        b.iter(|| {
+            let mut source = SmallReads(&data);
+            let mut buf = [0; 5];
+            let mut sum = 0;
+            while read_exact_reslicing(&mut source, &mut buf).unwrap() > 0 {
+                sum += buf[0] as usize;
+            }
+            sum
+        })
+    });
+    group.bench_function("index cursor, small reads", |b| {
+        b.iter(|| {
+            let mut source = SmallReads(&data);
+            let mut buf = [0; 5];
+            let mut sum = 0;
+            while read_exact_indexed(&mut source, &mut buf).unwrap() > 0 {
+                sum += buf[0] as usize;
+            }
+            sum
+        })
+    });
+    group.finish();
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let data = input(64 * 1024);
+
+    let mut group = c.benchmark_group("encode");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function("random 64 KiB", |b| {
+        let mut output = Vec::with_capacity(data.len() * 4);
+        b.iter(|| {
+            output.clear();
+            ecoji::encode(&mut data.as_slice(), &mut output).unwrap()
+        })
+    });
+    group.bench_function("random 64 KiB, small reads", |b| {
+        let mut output = Vec::with_capacity(data.len() * 4);
+        b.iter(|| {
+            output.clear();
+            ecoji::encode(&mut SmallReads(&data), &mut output).unwrap()
+        })
+    });
+
+    let zeros = vec![0; 1024 * 1024];
+    group.throughput(Throughput::Bytes(zeros.len() as u64));
+    group.bench_function("zeros 1 MiB", |b| {
+        let mut output = Vec::with_capacity(zeros.len() * 4);
+        b.iter(|| {
+            output.clear();
+            ecoji::encode(&mut zeros.as_slice(), &mut output).unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn bench_encode_to_string(c: &mut Criterion) {
+    let inputs: Vec<Vec<u8>> = (0..100).map(|i| input(4 * 1024 + i)).collect();
+
+    let mut group = c.benchmark_group("encode to string");
+    group.throughput(Throughput::Bytes(inputs.iter().map(|i| i.len() as u64).sum()));
+    group.bench_function("100 x 4 KiB, growing", |b| {
+        b.iter(|| {
+            inputs.iter()
+                .map(|i| ecoji::encode_to_string(&mut i.as_slice()).unwrap().len())
+                .sum::<usize>()
+        })
+    });
+    group.bench_function("100 x 4 KiB, presized", |b| {
+        b.iter(|| inputs.iter().map(|i| ecoji::encode_slice_to_string(i).len()).sum::<usize>())
+    });
+    group.finish();
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let data = input(64 * 1024);
+    let encoded = ecoji::encode_to_string(&mut data.as_slice()).unwrap();
+
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function("random 64 KiB", |b| {
+        let mut output = Vec::with_capacity(data.len());
+        b.iter(|| {
+            output.clear();
+            ecoji::decode(&mut encoded.as_bytes(), &mut output).unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_read_exact, bench_encode, bench_encode_to_string, bench_decode);
+criterion_main!(benches);
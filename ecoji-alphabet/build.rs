@@ -0,0 +1,90 @@
+#[cfg(feature = "phf")]
+extern crate phf_codegen;
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write, BufRead};
+use std::path::Path;
+use std::env;
+use std::error::Error;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    run("emojis.txt", "emojis.rs").expect("Failed to generate 'emojis.rs'");
+    run("emojis_safe.txt", "emojis_safe.rs").expect("Failed to generate 'emojis_safe.rs'");
+}
+
+/// Given an input file containing base-16 representations of emoji code points, generates
+/// an array of length 1024 of these emojis to serve as an alphabet for the ecoji encoding.
+///
+/// Padding characters are generated here as well.
+///
+/// With the `phf` feature, also generates a reverse mapping from code points to the indices of
+/// the respective code points in the alphabet array using the phf crate.
+///
+/// The input file must be sorted by code point, which is what makes the encoding preserve the
+/// sort order of the input data.
+fn run(input_file: &str, output_file: &str) -> Result<(), Box<dyn Error>> {
+    println!("cargo:rerun-if-changed={}", input_file);
+
+    let input = BufReader::new(File::open(input_file)?);
+    let mut lines: Vec<_> = input.lines().collect::<Result<_, _>>()?;
+
+    let out_dir = env::var("OUT_DIR")?;
+    let dest_path = Path::new(&out_dir).join(output_file);
+    let mut output = BufWriter::new(File::create(&dest_path)?);
+
+    let paddings = [
+        "2615".to_owned(),
+        "269C".to_owned(),
+        lines.remove(256),
+        lines.remove(512),
+        lines.remove(768),
+    ];
+    let padding_names = ["PADDING", "PADDING_40", "PADDING_41", "PADDING_42", "PADDING_43"];
+    for (name, padding) in padding_names.iter().zip(&paddings) {
+        writeln!(&mut output, r"pub const {}: char = '\u{{{}}}';", name, padding)?;
+    }
+
+    lines.truncate(1024);
+
+    writeln!(&mut output, "pub const EMOJIS: [char; 1024] = [")?;
+    for (i, line) in lines.iter().enumerate() {
+        writeln!(&mut output, r"    '\u{{{}}}',  // {}", line, i)?;
+    }
+    writeln!(&mut output, "];")?;
+
+    let widths = lines.iter().chain(&paddings)
+        .map(|line| Ok(parse_code_point(line)?.len_utf8()))
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    writeln!(&mut output, "/// The maximum length of the UTF-8 encoding of a code point of the alphabet, including paddings.")?;
+    writeln!(&mut output, "pub const MAX_EMOJI_UTF8_LEN: usize = {};", widths.iter().max().unwrap())?;
+    writeln!(&mut output, "/// The minimum length of the UTF-8 encoding of a code point of the alphabet, including paddings.")?;
+    writeln!(&mut output, "pub const MIN_EMOJI_UTF8_LEN: usize = {};", widths.iter().min().unwrap())?;
+
+    write_rev_map(&mut output, &lines)
+}
+
+fn parse_code_point(line: &str) -> Result<char, Box<dyn Error>> {
+    char::from_u32(u32::from_str_radix(line, 16)?)
+        .ok_or_else(|| format!("Invalid code point: {}", line).into())
+}
+
+#[cfg(feature = "phf")]
+fn write_rev_map<W: Write>(output: &mut W, lines: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut rev_map = phf_codegen::Map::new();
+    for (i, line) in lines.iter().enumerate() {
+        rev_map.entry(parse_code_point(line)?, &i.to_string());
+    }
+
+    write!(output, "pub static EMOJIS_REV: ::phf::Map<char, usize> = ")?;
+    rev_map.build(output)?;
+    writeln!(output, ";")?;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "phf"))]
+fn write_rev_map<W: Write>(_output: &mut W, _lines: &[String]) -> Result<(), Box<dyn Error>> {
+    Ok(())
+}
+
@@ -0,0 +1,19 @@
+//! Alphabet tables of the [`ecoji`](https://docs.rs/ecoji) crate.
+//!
+//! The tables are generated from lists of code points at build time. They live in a separate
+//! crate so that they are not recompiled every time the codec itself changes. This crate is
+//! an implementation detail of `ecoji` and should not be used directly.
+//!
+//! The `phf` feature, enabled by default, also generates perfect hash maps from code points
+//! to their indices. Without it the alphabets, which are sorted by code point, have to be
+//! searched instead.
+
+#[cfg(feature = "phf")]
+extern crate phf;
+
+include!(concat!(env!("OUT_DIR"), "/emojis.rs"));
+
+/// Tables of the alphabet which avoids code points combining with their neighbours.
+pub mod safe {
+    include!(concat!(env!("OUT_DIR"), "/emojis_safe.rs"));
+}
@@ -0,0 +1,377 @@
+#[cfg(all(feature = "runtime-reverse-map", not(feature = "phf")))]
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(all(feature = "runtime-reverse-map", not(feature = "phf")))]
+use std::hash::{BuildHasherDefault, Hasher};
+#[cfg(all(feature = "runtime-reverse-map", not(feature = "phf")))]
+use std::sync::OnceLock;
+
+#[cfg(feature = "phf")]
+use phf;
+
+use emojis;
+
+/// The number of code points in a group of encoded data.
+pub const CHARS_PER_GROUP: usize = 4;
+
+/// The number of input bytes encoded by a complete group; only the last group of the encoded
+/// data may encode fewer bytes.
+pub const BYTES_PER_GROUP: usize = 5;
+
+/// The number of bits of data encoded by each code point of the alphabet, which has
+/// `1 << BITS_PER_CHAR` code points.
+pub const BITS_PER_CHAR: usize = 10;
+
+/// The maximum length in bytes of the UTF-8 encoding of a code point of any of the alphabets,
+/// including paddings.
+///
+/// A buffer of `CHARS_PER_GROUP * MAX_EMOJI_UTF8_LEN` bytes is enough for any encoded group.
+pub const MAX_EMOJI_UTF8_LEN: usize = max(emojis::MAX_EMOJI_UTF8_LEN, emojis::safe::MAX_EMOJI_UTF8_LEN);
+
+/// The minimum length in bytes of the UTF-8 encoding of a code point of any of the alphabets,
+/// including paddings.
+pub const MIN_EMOJI_UTF8_LEN: usize = min(emojis::MIN_EMOJI_UTF8_LEN, emojis::safe::MIN_EMOJI_UTF8_LEN);
+
+const fn max(a: usize, b: usize) -> usize {
+    if a > b { a } else { b }
+}
+
+const fn min(a: usize, b: usize) -> usize {
+    if a < b { a } else { b }
+}
+
+/// An alphabet of the Ecoji encoding: 1024 code points representing 10 bits of data each,
+/// plus five padding code points.
+///
+/// All functions of this crate use [`Alphabet::STANDARD`](#associatedconstant.STANDARD), which
+/// is the alphabet of the reference Ecoji implementation. Other alphabets can be selected
+/// with [`EncodeOptions`](struct.EncodeOptions.html) and
+/// [`DecodeOptions`](struct.DecodeOptions.html). Data must be decoded with the same alphabet it
+/// was encoded with.
+#[derive(Clone, Copy)]
+pub struct Alphabet {
+    name: &'static str,
+    emojis: &'static [char; 1 << BITS_PER_CHAR],
+    #[cfg(feature = "phf")]
+    emojis_rev: &'static phf::Map<char, usize>,
+    #[cfg(all(feature = "runtime-reverse-map", not(feature = "phf")))]
+    emojis_rev: fn() -> &'static ReverseMap,
+    #[cfg(feature = "fast-encode")]
+    emojis_utf8: &'static Utf8Table,
+    padding: char,
+    padding_4x: [char; 4],
+}
+
+impl Alphabet {
+    /// The alphabet of the reference Ecoji implementation.
+    pub const STANDARD: Alphabet = Alphabet {
+        name: "standard",
+        emojis: &emojis::EMOJIS,
+        #[cfg(feature = "phf")]
+        emojis_rev: &emojis::EMOJIS_REV,
+        #[cfg(all(feature = "runtime-reverse-map", not(feature = "phf")))]
+        emojis_rev: standard_rev,
+        #[cfg(feature = "fast-encode")]
+        emojis_utf8: &STANDARD_UTF8,
+        padding: emojis::PADDING,
+        padding_4x: [emojis::PADDING_40, emojis::PADDING_41, emojis::PADDING_42, emojis::PADDING_43],
+    };
+
+    /// An alphabet which avoids code points that may combine with their neighbours or render
+    /// unreliably: regional indicators (adjacent pairs of which are displayed as flags), skin tone
+    /// modifiers and hair components. They are replaced with other emojis, so this alphabet is
+    /// not compatible with the reference implementation.
+    ///
+    /// Data encoded with this alphabet has the same properties as with the standard one: it can
+    /// be concatenated and it has the same sort order as the input data.
+    pub const SAFE: Alphabet = Alphabet {
+        name: "safe",
+        emojis: &emojis::safe::EMOJIS,
+        #[cfg(feature = "phf")]
+        emojis_rev: &emojis::safe::EMOJIS_REV,
+        #[cfg(all(feature = "runtime-reverse-map", not(feature = "phf")))]
+        emojis_rev: safe_rev,
+        #[cfg(feature = "fast-encode")]
+        emojis_utf8: &SAFE_UTF8,
+        padding: emojis::safe::PADDING,
+        padding_4x: [
+            emojis::safe::PADDING_40,
+            emojis::safe::PADDING_41,
+            emojis::safe::PADDING_42,
+            emojis::safe::PADDING_43,
+        ],
+    };
+
+    /// Returns the code point representing the given 10-bit value.
+    pub(crate) fn emoji(&self, bits: usize) -> char {
+        self.emojis[bits]
+    }
+
+    /// Returns the UTF-8 representation of the code point representing the given 10-bit value,
+    /// padded with zeros, and its length.
+    #[cfg(feature = "fast-encode")]
+    pub(crate) fn emoji_utf8(&self, bits: usize) -> &([u8; MAX_EMOJI_UTF8_LEN], usize) {
+        &self.emojis_utf8[bits]
+    }
+
+    /// Returns the 10-bit value represented by the given code point, if it is not a padding.
+    #[cfg(feature = "phf")]
+    pub(crate) fn bits(&self, c: char) -> Option<usize> {
+        self.emojis_rev.get(&c).cloned()
+    }
+
+    /// Returns the 10-bit value represented by the given code point, if it is not a padding.
+    ///
+    /// The reverse mapping is built on the first call for each alphabet.
+    #[cfg(all(feature = "runtime-reverse-map", not(feature = "phf")))]
+    pub(crate) fn bits(&self, c: char) -> Option<usize> {
+        (self.emojis_rev)().get(&c).cloned()
+    }
+
+    /// Returns the 10-bit value represented by the given code point, if it is not a padding.
+    ///
+    /// Alphabets are sorted by code point, so without the reverse mapping the code point can be
+    /// found with a binary search.
+    #[cfg(not(any(feature = "phf", feature = "runtime-reverse-map")))]
+    pub(crate) fn bits(&self, c: char) -> Option<usize> {
+        self.emojis.binary_search(&c).ok()
+    }
+
+    /// Returns the padding code point used to fill up groups.
+    pub(crate) fn padding(&self) -> char {
+        self.padding
+    }
+
+    /// Returns the padding code point ending a 4-byte group whose last byte has the given two
+    /// low bits.
+    pub(crate) fn padding_4x(&self, low_bits: usize) -> char {
+        self.padding_4x[low_bits]
+    }
+
+    /// Returns the two low bits of the last byte of a 4-byte group if the given code point
+    /// is one of the paddings ending such group.
+    pub(crate) fn padding_4x_bits(&self, c: char) -> Option<usize> {
+        self.padding_4x.iter().position(|&p| p == c)
+    }
+
+    /// Returns the index of the given character in the alphabet; padding characters follow the
+    /// alphabet proper, with the padding at 1024 and the 4-byte group paddings at 1025 to 1028.
+    pub(crate) fn index_of(&self, c: char) -> Option<usize> {
+        if c == self.padding {
+            Some(1024)
+        } else if let Some(bits) = self.padding_4x_bits(c) {
+            Some(1025 + bits)
+        } else {
+            self.bits(c)
+        }
+    }
+
+    /// Returns the character at the given index, as returned by `index_of`.
+    pub(crate) fn char_at(&self, index: usize) -> Option<char> {
+        match index {
+            0..=1023 => Some(self.emoji(index)),
+            1024 => Some(self.padding),
+            1025..=1028 => Some(self.padding_4x(index - 1025)),
+            _ => None,
+        }
+    }
+
+    /// Checks whether the given code point is a part of this alphabet, including paddings.
+    pub(crate) fn contains(&self, c: char) -> bool {
+        self.index_of(c).is_some()
+    }
+
+    /// Checks that the alphabet is sorted, that the reverse mapping of this alphabet is the exact
+    /// inverse of the alphabet itself, and that paddings are distinct from other code points.
+    fn is_consistent(&self) -> bool {
+        let paddings_distinct = self.padding_4x.iter()
+            .enumerate()
+            .all(|(i, &p)| p != self.padding && !self.padding_4x[..i].contains(&p));
+
+        paddings_distinct &&
+            self.rev_len_matches() &&
+            self.emojis.windows(2).all(|w| w[0] < w[1]) &&
+            self.emojis.iter().enumerate().all(|(i, &c)| self.bits(c) == Some(i)) &&
+            self.bits(self.padding).is_none() &&
+            self.padding_4x.iter().all(|&p| self.bits(p).is_none())
+    }
+
+    #[cfg(feature = "phf")]
+    fn rev_len_matches(&self) -> bool {
+        self.emojis_rev.len() == self.emojis.len()
+    }
+
+    #[cfg(all(feature = "runtime-reverse-map", not(feature = "phf")))]
+    fn rev_len_matches(&self) -> bool {
+        (self.emojis_rev)().len() == self.emojis.len()
+    }
+
+    #[cfg(not(any(feature = "phf", feature = "runtime-reverse-map")))]
+    fn rev_len_matches(&self) -> bool {
+        true
+    }
+}
+
+/// The UTF-8 representations of the code points of an alphabet, padded with zeros to the same
+/// size, and their lengths.
+#[cfg(feature = "fast-encode")]
+type Utf8Table = [([u8; MAX_EMOJI_UTF8_LEN], usize); 1 << BITS_PER_CHAR];
+
+#[cfg(feature = "fast-encode")]
+const STANDARD_UTF8: Utf8Table = utf8_table(&emojis::EMOJIS);
+
+#[cfg(feature = "fast-encode")]
+const SAFE_UTF8: Utf8Table = utf8_table(&emojis::safe::EMOJIS);
+
+/// Builds the table of UTF-8 representations of an alphabet at compile time.
+#[cfg(feature = "fast-encode")]
+const fn utf8_table(emojis: &[char; 1 << BITS_PER_CHAR]) -> Utf8Table {
+    let mut table = [([0; MAX_EMOJI_UTF8_LEN], 0); 1 << BITS_PER_CHAR];
+    let mut i = 0;
+    while i < table.len() {
+        let mut buf = [0; MAX_EMOJI_UTF8_LEN];
+        let len = emojis[i].encode_utf8(&mut buf).len();
+        table[i] = (buf, len);
+        i += 1;
+    }
+    table
+}
+
+/// A map from the code points of an alphabet to their indices, built at runtime.
+#[cfg(all(feature = "runtime-reverse-map", not(feature = "phf")))]
+type ReverseMap = HashMap<char, usize, BuildHasherDefault<CharHasher>>;
+
+/// A multiplicative hasher for code points.
+///
+/// The keys are a fixed set of code points rather than untrusted input, so the DoS resistance of
+/// the default hasher is not needed, and it would make lookups noticeably slower than with `phf`.
+#[cfg(all(feature = "runtime-reverse-map", not(feature = "phf")))]
+#[derive(Default)]
+struct CharHasher(u64);
+
+#[cfg(all(feature = "runtime-reverse-map", not(feature = "phf")))]
+impl Hasher for CharHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.write_u8(b);
+        }
+    }
+
+    fn write_u8(&mut self, b: u8) {
+        self.write_u32(b as u32);
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.0 = (self.0.rotate_left(5) ^ n as u64).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Builds the reverse map of an alphabet.
+#[cfg(all(feature = "runtime-reverse-map", not(feature = "phf")))]
+fn reverse_map(emojis: &[char]) -> ReverseMap {
+    emojis.iter().enumerate().map(|(i, &c)| (c, i)).collect()
+}
+
+#[cfg(all(feature = "runtime-reverse-map", not(feature = "phf")))]
+fn standard_rev() -> &'static ReverseMap {
+    static REV: OnceLock<ReverseMap> = OnceLock::new();
+    REV.get_or_init(|| reverse_map(&emojis::EMOJIS))
+}
+
+#[cfg(all(feature = "runtime-reverse-map", not(feature = "phf")))]
+fn safe_rev() -> &'static ReverseMap {
+    static REV: OnceLock<ReverseMap> = OnceLock::new();
+    REV.get_or_init(|| reverse_map(&emojis::safe::EMOJIS))
+}
+
+/// Verifies that the tables of all alphabets provided by this crate are consistent, i.e. that
+/// looking up any code point of an alphabet in its reverse mapping gives the index of this code
+/// point.
+///
+/// The tables are generated at compile time and checked by the test suite, so this function
+/// should always return `true`. It is provided as a defense-in-depth measure for deployments
+/// which want to check this invariant at startup, since a broken table would only cause
+/// failures on specific inputs.
+///
+/// # Examples
+///
+/// ```
+/// assert!(ecoji::verify_reverse_map());
+/// ```
+pub fn verify_reverse_map() -> bool {
+    [Alphabet::STANDARD, Alphabet::SAFE].iter().all(Alphabet::is_consistent)
+}
+
+impl fmt::Debug for Alphabet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Alphabet").field(&self.name).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safe_alphabet_has_no_combining_code_points() {
+        let is_combining = |c: char| matches!(c as u32, 0x1F1E6..=0x1F1FF | 0x1F3FB..=0x1F3FF | 0x1F9B0..=0x1F9B3);
+
+        assert!(Alphabet::STANDARD.emojis.iter().cloned().any(is_combining));
+        assert!(!Alphabet::SAFE.emojis.iter().cloned().any(is_combining));
+        assert!(!Alphabet::SAFE.padding_4x.iter().cloned().any(is_combining));
+    }
+
+    #[test]
+    fn test_verify_reverse_map() {
+        assert!(verify_reverse_map());
+
+        let mut broken = Alphabet::STANDARD;
+        broken.padding = broken.padding_4x[0];
+        assert!(!broken.is_consistent());
+
+        let mut broken = Alphabet::STANDARD;
+        broken.emojis = Alphabet::SAFE.emojis;
+        assert!(!broken.is_consistent());
+    }
+
+    #[test]
+    #[cfg(feature = "fast-encode")]
+    fn test_utf8_tables() {
+        for alphabet in &[Alphabet::STANDARD, Alphabet::SAFE] {
+            for (bits, c) in alphabet.emojis.iter().enumerate() {
+                let &(ref utf8, len) = alphabet.emoji_utf8(bits);
+                assert_eq!(&utf8[..len], c.to_string().as_bytes());
+                assert!(utf8[len..].iter().all(|&b| b == 0));
+            }
+        }
+    }
+
+    #[test]
+    fn test_group_constants() {
+        assert_eq!(BYTES_PER_GROUP * 8, CHARS_PER_GROUP * BITS_PER_CHAR);
+        assert_eq!(Alphabet::STANDARD.emojis.len(), 1 << BITS_PER_CHAR);
+    }
+
+    #[test]
+    fn test_index_of() {
+        for alphabet in &[Alphabet::STANDARD, Alphabet::SAFE] {
+            for i in 0..1024 {
+                assert_eq!(alphabet.index_of(alphabet.emoji(i)), Some(i));
+            }
+            assert_eq!(alphabet.index_of(alphabet.padding()), Some(1024));
+            for i in 0..4 {
+                assert_eq!(alphabet.index_of(alphabet.padding_4x(i)), Some(1025 + i));
+            }
+            assert_eq!(alphabet.index_of('a'), None);
+
+            for i in 0..1029 {
+                assert_eq!(alphabet.char_at(i).and_then(|c| alphabet.index_of(c)), Some(i));
+            }
+            assert_eq!(alphabet.char_at(1029), None);
+        }
+    }
+}
@@ -1,28 +1,347 @@
 extern crate ecoji;
 #[macro_use]
 extern crate clap;
+#[cfg(feature = "mmap")]
+extern crate memmap2;
 
-use std::io;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process;
 
-use clap::{App, AppSettings};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use ecoji::{CountingReader, EncodeOptions, DEFAULT_BUFFER_SIZE};
 
 fn main() {
+    let io_args = "--stats 'Print statistics about the processed data to standard error'
+                   --buffer-size [BYTES] 'Size of the input and output buffers; 0 disables buffering [default: 8192]'
+                   [FILE] 'Input file; standard input is read if not specified'";
+    // The output never depends on the environment, e.g. on whether it is a terminal; the flag
+    // only lets scripts state that they rely on this
+    let deterministic_arg = "--deterministic 'Guarantee byte-identical output regardless of the environment, e.g. for golden tests'";
+    let text_arg = "--text 'Treat decoded data as text and show control characters other than newlines and tabs in caret notation'";
+
     let matches = App::new("ecoji")
         .version(crate_version!())
         .author("Vladimir Matveev <vladimir.matweev@gmail.com>")
         .about(
-            "Encode or decode data in standard input as emojis and print results to standard output.\n\
-             A Rust reimplementation of the original Ecoji library and tool (https://github.com/keith-turner/ecoji)."
+            "Encode or decode data in FILE or standard input as emojis and print results to standard output.\n\
+             A Rust reimplementation of the original Ecoji library and tool (https://github.com/keith-turner/ecoji).\n\
+             \n\
+             Without a subcommand, data is encoded, or decoded if -d is given.\n\
+             \n\
+             The output is always exactly the encoded or decoded data: no trailing newline is added, \
+             and nothing depends on whether standard output is a terminal.\n\
+             \n\
+             Data is processed as a stream, so memory usage is bounded by the buffer size regardless \
+             of the input size (except for --self-check, which keeps the whole input in memory)."
         )
         .setting(AppSettings::ColoredHelp)
-        .args_from_usage("-d, --decode 'Decode data'")
+        .setting(AppSettings::VersionlessSubcommands)
+        .args_from_usage(
+            "-d, --decode 'Decode data (deprecated, use the decode subcommand instead)'"
+        )
+        .args_from_usage(io_args)
+        .arg(Arg::from_usage(text_arg).requires("decode"))
+        .args_from_usage(deterministic_arg)
+        .arg(
+            Arg::from_usage("--self-check 'Encode and decode the input and check that the result matches it'")
+                .conflicts_with("decode")
+        )
+        .subcommand(
+            SubCommand::with_name("encode")
+                .about("Encode data as emojis")
+                .setting(AppSettings::ColoredHelp)
+                .args_from_usage(io_args)
+                .args_from_usage(deterministic_arg)
+        )
+        .subcommand(
+            SubCommand::with_name("decode")
+                .about("Decode data from emojis")
+                .setting(AppSettings::ColoredHelp)
+                .args_from_usage(io_args)
+                .args_from_usage(text_arg)
+                .args_from_usage(deterministic_arg)
+        )
         .get_matches();
 
-    let (stdin, stdout) = (io::stdin(), io::stdout());
-    let (mut stdin, mut stdout) = (stdin.lock(), stdout.lock());
-    if matches.is_present("decode") {
-        ecoji::decode(&mut stdin, &mut stdout).expect("Failed to decode data");
+    match matches.subcommand() {
+        ("encode", Some(args)) => run_encode(args),
+        ("decode", Some(args)) => run_decode(args),
+        _ if matches.is_present("self-check") => run_self_check(&matches),
+        _ if matches.is_present("decode") => run_decode(&matches),
+        _ => run_encode(&matches),
+    }
+}
+
+fn run_encode(args: &ArgMatches) {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let buffer_size = buffer_size(args);
+    let (input_bytes, output_bytes) = encode_input(args.value_of("FILE"), &mut stdout, buffer_size)
+        .unwrap_or_else(|e| fail("Failed to encode data", e));
+    if args.is_present("stats") {
+        print_encode_stats(input_bytes, output_bytes as u64);
+    }
+}
+
+fn run_decode(args: &ArgMatches) {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let buffer_size = buffer_size(args);
+    let mut input = CountingReader::new(open_input(args.value_of("FILE")).unwrap_or_else(|e| fail("Failed to open input", e)));
+    let stats = if args.is_present("text") {
+        decode_stream(&mut input, &mut CaretWriter(&mut stdout), buffer_size)
     } else {
-        ecoji::encode(&mut stdin, &mut stdout).expect("Failed to encode data");
+        decode_stream(&mut input, &mut stdout, buffer_size)
+    }.unwrap_or_else(|e| fail("Failed to decode data", e));
+    if args.is_present("stats") {
+        print_decode_stats(input.bytes_read(), &stats);
+    }
+}
+
+/// Counts of the data consumed and produced by decoding.
+#[derive(Debug, Default, PartialEq)]
+struct DecodeStats {
+    input_chars: u64,
+    padding_chars: u64,
+    output_bytes: u64,
+}
+
+/// Decodes the input group by group through buffers of the given size and counts the code points
+/// of every group, since concatenated or wrapped input may contain any number of paddings.
+fn decode_stream<R: Read, W: Write>(input: R, output: &mut W, buffer_size: usize) -> io::Result<DecodeStats> {
+    if buffer_size == 0 {
+        return decode_groups_counting(input, output);
+    }
+
+    let mut output = io::BufWriter::with_capacity(buffer_size, output);
+    let stats = decode_groups_counting(io::BufReader::with_capacity(buffer_size, input), &mut output)?;
+    output.flush()?;
+    Ok(stats)
+}
+
+fn decode_groups_counting<R: Read, W: Write>(input: R, output: &mut W) -> io::Result<DecodeStats> {
+    let mut stats = DecodeStats::default();
+    for group in ecoji::decode_groups(input) {
+        let group = group?;
+        output.write_all(&group)?;
+
+        // Every group has four code points and ends with paddings unless it decodes to five
+        // bytes; a 4-byte group ends with one of the special 4-byte paddings
+        stats.input_chars += 4;
+        stats.padding_chars += match group.len() {
+            5 => 0,
+            4 | 3 => 1,
+            2 => 2,
+            _ => 3,
+        };
+        stats.output_bytes += group.len() as u64;
+    }
+    Ok(stats)
+}
+
+fn run_self_check(args: &ArgMatches) {
+    let mut input = Vec::new();
+    open_input(args.value_of("FILE"))
+        .and_then(|mut source| source.read_to_end(&mut input))
+        .unwrap_or_else(|e| fail("Failed to read input", e));
+
+    let encoded = ecoji::encode_to_string(&mut input.as_slice()).unwrap_or_else(|e| fail("Failed to encode data", e));
+    let decoded = ecoji::decode_str(&encoded).unwrap_or_else(|e| fail("Failed to decode data", e));
+
+    match first_divergence(&input, &decoded) {
+        None => println!("OK: {} bytes round-tripped through {} characters", input.len(), encoded.chars().count()),
+        Some(offset) => {
+            let describe = |data: &[u8]| match data.get(offset) {
+                Some(b) => format!("{:#04x}", b),
+                None => "end of data".to_owned(),
+            };
+            eprintln!(
+                "ecoji: Self-check failed: decoded data differs from input at byte {}: expected {}, got {}",
+                offset, describe(&input), describe(&decoded)
+            );
+            process::exit(1)
+        }
+    }
+}
+
+/// Returns the offset of the first byte at which the given slices differ, if any.
+fn first_divergence(a: &[u8], b: &[u8]) -> Option<usize> {
+    match a.iter().zip(b).position(|(x, y)| x != y) {
+        Some(offset) => Some(offset),
+        None if a.len() != b.len() => Some(a.len().min(b.len())),
+        None => None,
+    }
+}
+
+/// Returns the buffer size given with `--buffer-size`, or the default one.
+fn buffer_size(args: &ArgMatches) -> usize {
+    if args.is_present("buffer-size") {
+        value_t!(args, "buffer-size", usize).unwrap_or_else(|e| e.exit())
+    } else {
+        DEFAULT_BUFFER_SIZE
+    }
+}
+
+/// Prints the error on a single line to standard error and exits with a failure status.
+fn fail(context: &str, error: io::Error) -> ! {
+    eprintln!("ecoji: {}: {}", context, error);
+    process::exit(1)
+}
+
+fn open_input(file: Option<&str>) -> io::Result<Box<dyn Read>> {
+    match file {
+        Some(path) => Ok(Box::new(File::open(path)?)),
+        None => Ok(Box::new(io::stdin().lock())),
+    }
+}
+
+/// Encodes the input and returns the number of bytes read and written.
+#[cfg(not(feature = "mmap"))]
+fn encode_input<W: Write>(file: Option<&str>, output: &mut W, buffer_size: usize) -> io::Result<(u64, usize)> {
+    encode_stream(open_input(file)?, output, buffer_size)
+}
+
+/// Encodes the input and returns the number of bytes read and written.
+///
+/// An input file is mapped into memory and encoded as a single slice, without any read calls;
+/// the pages of the mapping are loaded on demand and can be evicted by the OS, so this does not
+/// bound the memory usage any less than reading.
+#[cfg(feature = "mmap")]
+fn encode_input<W: Write>(file: Option<&str>, output: &mut W, buffer_size: usize) -> io::Result<(u64, usize)> {
+    match file {
+        Some(path) => {
+            let file = File::open(path)?;
+            // The mapping is only valid as long as the file is not modified concurrently, which
+            // is the same caveat as with any other tool reading a file being written to
+            let map = unsafe { memmap2::Mmap::map(&file)? };
+            let mut output = io::BufWriter::with_capacity(buffer_size, output);
+            let output_bytes = ecoji::encode_vectored(&[&map], &mut output)?;
+            output.flush()?;
+            Ok((map.len() as u64, output_bytes))
+        }
+        None => encode_stream(open_input(file)?, output, buffer_size),
+    }
+}
+
+/// Encodes the input group by group through buffers of the given size, so that only a bounded
+/// amount of data is in memory at any time, and returns the number of bytes read and written.
+fn encode_stream<R: Read, W: Write>(input: R, output: &mut W, buffer_size: usize) -> io::Result<(u64, usize)> {
+    let mut input = CountingReader::new(input);
+    let output_bytes = EncodeOptions::new().buffer_size(buffer_size).encode(&mut input, output)?;
+    Ok((input.bytes_read(), output_bytes))
+}
+
+/// A writer which replaces ASCII control characters, except for newlines and tabs, with their
+/// caret notation (e.g. `^[` for the escape character), so decoded text can be printed to
+/// a terminal safely.
+struct CaretWriter<W>(W);
+
+impl<W: Write> Write for CaretWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut escaped = Vec::with_capacity(buf.len());
+        for &b in buf {
+            match b {
+                b'\n' | b'\t' => escaped.push(b),
+                0x00..=0x1f => escaped.extend_from_slice(&[b'^', b + 0x40]),
+                0x7f => escaped.extend_from_slice(b"^?"),
+                _ => escaped.push(b),
+            }
+        }
+        self.0.write_all(&escaped)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+fn print_encode_stats(input_bytes: u64, output_bytes: u64) {
+    eprintln!("input bytes: {}", input_bytes);
+    eprintln!("output bytes: {}", output_bytes);
+    eprintln!("output characters: {}", ecoji::encoded_chars(input_bytes as usize));
+    if input_bytes > 0 {
+        eprintln!("expansion ratio: {:.2}", output_bytes as f64 / input_bytes as f64);
+    }
+}
+
+fn print_decode_stats(input_bytes: u64, stats: &DecodeStats) {
+    eprintln!("input bytes: {}", input_bytes);
+    eprintln!("input characters: {}", stats.input_chars);
+    eprintln!("output bytes: {}", stats.output_bytes);
+    eprintln!("padding characters: {}", stats.padding_chars);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_divergence() {
+        assert_eq!(first_divergence(b"abc", b"abc"), None);
+        assert_eq!(first_divergence(b"", b""), None);
+        assert_eq!(first_divergence(b"abc", b"abd"), Some(2));
+        assert_eq!(first_divergence(b"abc", b"ab"), Some(2));
+        assert_eq!(first_divergence(b"a", b"abc"), Some(1));
+    }
+
+    /// Returns the peak resident set size of the current process in kilobytes.
+    #[cfg(target_os = "linux")]
+    fn peak_rss_kb() -> Option<u64> {
+        let status = ::std::fs::read_to_string("/proc/self/status").ok()?;
+        let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+        line.split_whitespace().nth(1)?.parse().ok()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn peak_rss_kb() -> Option<u64> {
+        None
+    }
+
+    #[test]
+    fn test_encode_stream_bounded_memory() {
+        const INPUT_BYTES: u64 = 300 * 1024 * 1024;
+
+        let before = peak_rss_kb();
+        let mut output = ecoji::SinkWriter::new();
+        let (input_bytes, output_bytes) =
+            encode_stream(io::repeat(0xa5).take(INPUT_BYTES), &mut output, DEFAULT_BUFFER_SIZE).unwrap();
+        assert_eq!(input_bytes, INPUT_BYTES);
+        assert_eq!(output_bytes as u64, output.count());
+        assert!(output.count() > INPUT_BYTES);
+
+        // Keeping either the input or the output in memory would grow the peak by hundreds of
+        // megabytes; the tests running concurrently only need a few
+        if let (Some(before), Some(after)) = (before, peak_rss_kb()) {
+            assert!(after - before < 32 * 1024, "peak RSS grew from {} kB to {} kB", before, after);
+        }
+    }
+
+    #[test]
+    fn test_decode_stats() {
+        let cases = [
+            ("👶😲🇲👅🍉🔙🌥🌩", 8, 0, 10),
+            ("👶😲🇲👅\n🍉🔙🌥🌩\n👖📸🎈☕", 12, 1, 13),
+            // Concatenated encodings of single bytes
+            ("👕☕☕☕👙☕☕☕", 8, 6, 2),
+            ("", 0, 0, 0),
+        ];
+        for &(input, input_chars, padding_chars, output_bytes) in &cases {
+            for &buffer_size in &[0, 3, DEFAULT_BUFFER_SIZE] {
+                let mut output = Vec::new();
+                let stats = decode_stream(input.as_bytes(), &mut output, buffer_size).unwrap();
+                assert_eq!(stats, DecodeStats { input_chars, padding_chars, output_bytes });
+                assert_eq!(output, ecoji::decode_str(input).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_caret_writer() {
+        let mut output = Vec::new();
+        CaretWriter(&mut output).write_all(b"line\tone\r\n\x1b[31m\x00\x7f ok \xe2\x9c\x93").unwrap();
+        assert_eq!(output, &b"line\tone^M\n^[[31m^@^? ok \xe2\x9c\x93"[..]);
     }
 }
@@ -0,0 +1,97 @@
+//! Encoding of bit-level data, available with the `bitvec` feature.
+
+use std::io;
+
+use bitvec::prelude::*;
+
+use decode::decode_str;
+use encode::encode_to_string;
+
+/// Encodes a sequence of bits into the Ecoji format, returning the result as a new string.
+///
+/// Ecoji encodes whole bytes, so the bits are packed into bytes, most significant bit first, and
+/// the last byte is padded with zero bits. To recover exactly the original number of bits, the
+/// number of padding bits is stored in an additional byte preceding the packed data; therefore
+/// the output is only meant to be decoded with [`decode_bits`](fn.decode_bits.html).
+///
+/// This function is only available with the `bitvec` feature.
+///
+/// # Examples
+///
+/// ```
+/// use ecoji::bitvec::prelude::*;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let bits = bits![u8, Msb0; 1, 0, 1, 1, 0, 0, 1, 1, 1, 0, 1];
+///
+/// let output = ecoji::encode_bits(bits);
+/// assert_eq!(ecoji::decode_bits(&output)?, bits);
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn encode_bits(bits: &BitSlice<u8, Msb0>) -> String {
+    let padding_bits = (8 - bits.len() % 8) % 8;
+
+    let mut bytes = vec![0u8; 1 + bits.len().div_ceil(8)];
+    bytes[0] = padding_bits as u8;
+    bytes[1..].view_bits_mut::<Msb0>()[..bits.len()].copy_from_bitslice(bits);
+
+    encode_to_string(&mut bytes.as_slice()).expect("Reading from a slice cannot fail")
+}
+
+/// Decodes a sequence of bits encoded with [`encode_bits`](fn.encode_bits.html).
+///
+/// In addition to the [`decode`](fn.decode.html) failure conditions, returns an error of the
+/// `InvalidData` kind if the decoded data does not start with a valid number of padding bits,
+/// e.g. when the input is empty or was not produced by `encode_bits`.
+///
+/// This function is only available with the `bitvec` feature.
+pub fn decode_bits(input: &str) -> io::Result<BitVec<u8, Msb0>> {
+    let bytes = decode_str(input)?;
+    let padding_bits = match bytes.split_first() {
+        Some((&padding_bits, data)) if padding_bits < 8 && (padding_bits == 0 || !data.is_empty()) =>
+            padding_bits as usize,
+        _ => return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Decoded data does not start with a valid number of padding bits"
+        )),
+    };
+
+    let mut bits = BitVec::from_vec(bytes[1..].to_vec());
+    let len = bits.len() - padding_bits;
+    bits.truncate(len);
+    Ok(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bits_round_trip() {
+        let data: Vec<u8> = (0..7).map(|i| i * 37 + 5).collect();
+        let all_bits = data.view_bits::<Msb0>();
+        for len in 0..all_bits.len() {
+            let encoded = encode_bits(&all_bits[..len]);
+            assert_eq!(decode_bits(&encoded).unwrap(), all_bits[..len]);
+        }
+    }
+
+    #[test]
+    fn test_bits_unaligned_slice() {
+        let data = [0b1010_1100u8, 0b0111_0001];
+        let bits = &data.view_bits::<Msb0>()[3..13];
+        assert_eq!(decode_bits(&encode_bits(bits)).unwrap(), bits);
+    }
+
+    #[test]
+    fn test_invalid_padding_bits() {
+        for input in &[&b""[..], &[8, 0xff], &[3]] {
+            let encoded = encode_to_string(&mut &input[..]).unwrap();
+            let error = decode_bits(&encoded).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+        assert!(decode_bits(&encode_to_string(&mut &[0][..]).unwrap()).unwrap().is_empty());
+    }
+}
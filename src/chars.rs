@@ -5,27 +5,59 @@ use std::io::*;
 use std::fmt;
 use std::error as std_error;
 use std::str;
+use std::char;
 
-#[derive(Debug)]
+use error::DecodeError;
+
+#[derive(Debug, Clone)]
 pub struct Chars<R> {
     inner: R,
+    offset: u64,
 }
 
 impl<R> Chars<R> {
     pub fn new(inner: R) -> Chars<R> {
-        Chars { inner, }
+        Chars { inner, offset: 0, }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Makes offsets in subsequent errors relative to the current position of the stream.
+    pub fn reset_offset(&mut self) {
+        self.offset = 0;
     }
 }
 
 #[derive(Debug)]
 pub enum CharsError {
-    NotUtf8,
+    /// The bytes starting at the given offset of the byte stream are not a valid UTF-8 sequence;
+    /// `bytes` contains the lead byte and those following it which were read.
+    NotUtf8 { offset: u64, bytes: Vec<u8> },
+    /// The code unit at the given offset of a UTF-16 sequence is a surrogate without a pair.
+    UnpairedSurrogate { offset: u64, unit: u16 },
+    /// The number at the given index of a sequence of code points is not a Unicode scalar value.
+    InvalidCodePoint { offset: u64, value: u32 },
     Other(Error),
 }
 
 impl CharsError {
+    /// Converts this error into an I/O error; errors of the underlying reader are reported as
+    /// source errors with their original kind.
     pub fn into_io(self) -> Error {
-        Error::new(ErrorKind::InvalidData, self)
+        match self {
+            CharsError::Other(e) => DecodeError::wrap_source(e),
+            e => Error::new(ErrorKind::InvalidData, e),
+        }
+    }
+
+    fn not_utf8(offset: u64, bytes: &[u8]) -> CharsError {
+        CharsError::NotUtf8 { offset, bytes: bytes.to_vec() }
     }
 }
 
@@ -37,16 +69,21 @@ impl<R: Read> Iterator for Chars<R> {
             Ok(b) => b,
             Err(e) => return Some(Err(CharsError::Other(e))),
         };
+        let offset = self.offset;
+        self.offset += 1;
         let width = utf8_char_width(first_byte);
         if width == 1 { return Some(Ok(first_byte as char)) }
-        if width == 0 { return Some(Err(CharsError::NotUtf8)) }
+        if width == 0 { return Some(Err(CharsError::not_utf8(offset, &[first_byte]))) }
         let mut buf = [first_byte, 0, 0, 0];
         {
             let mut start = 1;
             while start < width {
                 match self.inner.read(&mut buf[start..width]) {
-                    Ok(0) => return Some(Err(CharsError::NotUtf8)),
-                    Ok(n) => start += n,
+                    Ok(0) => return Some(Err(CharsError::not_utf8(offset, &buf[..start]))),
+                    Ok(n) => {
+                        start += n;
+                        self.offset += n as u64;
+                    }
                     Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
                     Err(e) => return Some(Err(CharsError::Other(e))),
                 }
@@ -54,22 +91,105 @@ impl<R: Read> Iterator for Chars<R> {
         }
         Some(match str::from_utf8(&buf[..width]).ok() {
             Some(s) => Ok(s.chars().next().unwrap()),
-            None => Err(CharsError::NotUtf8),
+            None => Err(CharsError::not_utf8(offset, &buf[..width])),
         })
     }
 }
 
-impl std_error::Error for CharsError {
-    fn description(&self) -> &str {
-        match *self {
-            CharsError::NotUtf8 => "invalid utf8 encoding",
-            CharsError::Other(ref e) => std_error::Error::description(e),
+/// An iterator over characters assembled from an iterator of UTF-8 bytes, which behaves exactly
+/// like `Chars`, except that it reads bytes from an iterator rather than from a reader.
+#[derive(Debug, Clone)]
+pub struct ByteChars<I> {
+    inner: I,
+    offset: u64,
+}
+
+impl<I> ByteChars<I> {
+    pub fn new(inner: I) -> ByteChars<I> {
+        ByteChars { inner, offset: 0, }
+    }
+}
+
+impl<I: Iterator<Item = Result<u8>>> ByteChars<I> {
+    fn next_byte(&mut self) -> Option<Result<u8>> {
+        loop {
+            return match self.inner.next() {
+                Some(Err(ref e)) if e.kind() == ErrorKind::Interrupted => continue,
+                Some(Ok(b)) => {
+                    self.offset += 1;
+                    Some(Ok(b))
+                }
+                other => other,
+            };
         }
     }
-    fn cause(&self) -> Option<&std_error::Error> {
+}
+
+impl<I: Iterator<Item = Result<u8>>> Iterator for ByteChars<I> {
+    type Item = result::Result<char, CharsError>;
+
+    fn next(&mut self) -> Option<result::Result<char, CharsError>> {
+        let offset = self.offset;
+        let first_byte = match self.next_byte()? {
+            Ok(b) => b,
+            Err(e) => return Some(Err(CharsError::Other(e))),
+        };
+        let width = utf8_char_width(first_byte);
+        if width == 1 { return Some(Ok(first_byte as char)) }
+        if width == 0 { return Some(Err(CharsError::not_utf8(offset, &[first_byte]))) }
+        let mut buf = [first_byte, 0, 0, 0];
+        for i in 1..width {
+            match self.next_byte() {
+                Some(Ok(next)) => buf[i] = next,
+                Some(Err(e)) => return Some(Err(CharsError::Other(e))),
+                None => return Some(Err(CharsError::not_utf8(offset, &buf[..i]))),
+            }
+        }
+        Some(match str::from_utf8(&buf[..width]).ok() {
+            Some(s) => Ok(s.chars().next().unwrap()),
+            None => Err(CharsError::not_utf8(offset, &buf[..width])),
+        })
+    }
+}
+
+/// An iterator over characters assembled from an iterator of UTF-16 code units, which reports
+/// errors like `Chars`, with offsets counted in code units.
+#[derive(Debug, Clone)]
+pub struct Utf16Chars<I: Iterator<Item = u16>> {
+    inner: char::DecodeUtf16<I>,
+    offset: u64,
+}
+
+impl<I: Iterator<Item = u16>> Utf16Chars<I> {
+    pub fn new<T: IntoIterator<IntoIter = I, Item = u16>>(inner: T) -> Utf16Chars<I> {
+        Utf16Chars { inner: char::decode_utf16(inner), offset: 0 }
+    }
+}
+
+impl<I: Iterator<Item = u16>> Iterator for Utf16Chars<I> {
+    type Item = result::Result<char, CharsError>;
+
+    fn next(&mut self) -> Option<result::Result<char, CharsError>> {
+        let offset = self.offset;
+        Some(match self.inner.next()? {
+            Ok(c) => {
+                self.offset += c.len_utf16() as u64;
+                Ok(c)
+            }
+            Err(e) => {
+                self.offset += 1;
+                Err(CharsError::UnpairedSurrogate { offset, unit: e.unpaired_surrogate() })
+            }
+        })
+    }
+}
+
+impl std_error::Error for CharsError {
+    fn source(&self) -> Option<&(dyn std_error::Error + 'static)> {
         match *self {
-            CharsError::NotUtf8 => None,
-            CharsError::Other(ref e) => e.cause(),
+            CharsError::NotUtf8 { .. } | CharsError::UnpairedSurrogate { .. } |
+            CharsError::InvalidCodePoint { .. } => None,
+            CharsError::Other(ref e) => e.source(),
         }
     }
 }
@@ -77,13 +197,22 @@ impl std_error::Error for CharsError {
 impl fmt::Display for CharsError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            CharsError::NotUtf8 => "byte stream did not contain valid utf8".fmt(f),
+            CharsError::NotUtf8 { offset, ref bytes } => write!(
+                f, "byte stream did not contain valid utf8: invalid byte sequence {:02x?} at offset {}",
+                bytes, offset
+            ),
+            CharsError::UnpairedSurrogate { offset, unit } => write!(
+                f, "utf16 data contained an unpaired surrogate {:#06x} at offset {}", unit, offset
+            ),
+            CharsError::InvalidCodePoint { offset, value } => write!(
+                f, "code point {:#x} at offset {} is not a valid unicode scalar value", value, offset
+            ),
             CharsError::Other(ref e) => e.fmt(f),
         }
     }
 }
 
-fn read_one_byte(reader: &mut Read) -> Option<Result<u8>> {
+fn read_one_byte(reader: &mut dyn Read) -> Option<Result<u8>> {
     let mut buf = [0];
     loop {
         return match reader.read(&mut buf) {
@@ -116,6 +245,6 @@ static UTF8_CHAR_WIDTH: [u8; 256] = [
 ];
 
 fn utf8_char_width(b: u8) -> usize {
-    return UTF8_CHAR_WIDTH[b as usize] as usize;
+    UTF8_CHAR_WIDTH[b as usize] as usize
 }
 
@@ -0,0 +1,122 @@
+//! Opt-in integrity checking of encoded data with a trailing checksum byte.
+//!
+//! This is a lightweight layer over the codec for data transmitted over lossy channels, e.g.
+//! emojis read aloud or typed by hand. Data with a checksum is not a plain Ecoji encoding of the
+//! original data, so it must be decoded with [`decode_checked`](fn.decode_checked.html).
+
+use std::io;
+
+use decode::decode_str;
+use encode::encode_append;
+use error::DecodeError;
+
+/// Computes the CRC-8/SMBUS checksum of the data.
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0, |crc, &b| {
+        (0..8).fold(crc ^ b, |crc, _| if crc & 0x80 != 0 { crc << 1 ^ 0x07 } else { crc << 1 })
+    })
+}
+
+/// Appends a CRC-8 checksum byte to the input and encodes the result into the Ecoji format,
+/// returning it as a new string.
+///
+/// The checksum is CRC-8 with the polynomial x⁸ + x² + x + 1 (0x07), an initial value of zero,
+/// no reflection and no final XOR, also known as CRC-8/SMBUS; its check value, the checksum of
+/// the ASCII string `123456789`, is 0xF4. It detects all single-bit errors and all burst errors
+/// of up to eight bits, and other corruptions with probability 255/256. It is not a protection
+/// against deliberate tampering.
+///
+/// The output is not a plain Ecoji encoding of the input, so it must be decoded with
+/// [`decode_checked`](fn.decode_checked.html).
+///
+/// # Examples
+///
+/// ```
+/// let output = ecoji::encode_with_checksum(b"input data");
+///
+/// assert_eq!(ecoji::decode_checked(&output).unwrap(), b"input data");
+/// assert_eq!(ecoji::decode_str(&output).unwrap().len(), 11);
+/// ```
+pub fn encode_with_checksum(input: &[u8]) -> String {
+    let mut output = Vec::new();
+    encode_append(&[input, &[crc8(input)]].concat(), &mut output);
+    // encoded output is guaranteed to be valid UTF-8
+    unsafe { String::from_utf8_unchecked(output) }
+}
+
+/// Decodes data encoded with [`encode_with_checksum`](fn.encode_with_checksum.html), verifies its
+/// checksum and returns the decoded data without the checksum byte.
+///
+/// In addition to the [`decode`](fn.decode.html) failure conditions, this function returns an
+/// error of the `InvalidData` kind wrapping
+/// [`DecodeError::ChecksumMismatch`](enum.DecodeError.html) if the checksum does not match the
+/// data, or [`DecodeError::MissingChecksum`](enum.DecodeError.html) if the decoded data is empty.
+///
+/// # Examples
+///
+/// ```
+/// use ecoji::DecodeError;
+///
+/// // One code point of the encoding of "input data" with a checksum replaced by its neighbour
+/// let corrupted = ecoji::encode_with_checksum(b"input data").replacen('😲', "😳", 1);
+///
+/// let error = ecoji::decode_checked(&corrupted).unwrap_err();
+/// match error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()) {
+///     Some(&DecodeError::ChecksumMismatch { .. }) => {}
+///     _ => panic!("Unexpected error: {}", error),
+/// }
+/// ```
+pub fn decode_checked(input: &str) -> io::Result<Vec<u8>> {
+    let mut data = decode_str(input)?;
+    let expected = data.pop().ok_or_else(|| DecodeError::MissingChecksum.into_io())?;
+    let actual = crc8(&data);
+    if actual != expected {
+        return Err(DecodeError::ChecksumMismatch { expected, actual }.into_io());
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc8_check_value() {
+        assert_eq!(crc8(b""), 0);
+        assert_eq!(crc8(b"123456789"), 0xf4);
+    }
+
+    #[test]
+    fn test_checksum_round_trip() {
+        let data: Vec<u8> = (0..24).map(|i| i * 11).collect();
+        for len in 0..data.len() {
+            let encoded = encode_with_checksum(&data[..len]);
+            assert_eq!(decode_checked(&encoded).unwrap(), &data[..len]);
+        }
+    }
+
+    #[test]
+    fn test_corrupted_data() {
+        let encoded = encode_with_checksum(b"some input data");
+        let decoded = decode_str(&encoded).unwrap();
+
+        // Flipping any single bit of the decoded data must be detected
+        for i in 0..decoded.len() * 8 {
+            let mut corrupted = decoded.clone();
+            corrupted[i / 8] ^= 0x80 >> (i % 8);
+            let reencoded = ::encode_to_string(&mut corrupted.as_slice()).unwrap();
+
+            let error = decode_checked(&reencoded).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            match error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()) {
+                Some(&DecodeError::ChecksumMismatch { expected, actual }) => assert_ne!(expected, actual),
+                other => panic!("Unexpected error: {:?}", other),
+            }
+        }
+
+        assert_eq!(
+            decode_checked("").unwrap_err().get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+            Some(&DecodeError::MissingChecksum)
+        );
+    }
+}
@@ -0,0 +1,82 @@
+//! Conversion between Ecoji and base64, available with the `base64` feature.
+
+use std::io;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use decode::decode_str;
+use encode::encode_slice_to_string;
+
+/// Converts base64-encoded data into the Ecoji format.
+///
+/// The input is decoded with the standard base64 alphabet with padding, as described in
+/// RFC 4648, and the decoded bytes are encoded with [`encode`](fn.encode.html).
+///
+/// Returns an error of the `InvalidData` kind wrapping the `base64::DecodeError` if the input is
+/// not valid base64.
+///
+/// This function is only available with the `base64` feature.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// assert_eq!(ecoji::base64_to_ecoji("aW5wdXQgZGF0YQ==")?, "👶😲🇲👅🍉🔙🌥🌩");
+///
+/// assert!(ecoji::base64_to_ecoji("not base64!").is_err());
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn base64_to_ecoji(b64: &str) -> io::Result<String> {
+    let data = STANDARD.decode(b64).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(encode_slice_to_string(&data))
+}
+
+/// Converts Ecoji-encoded data into base64.
+///
+/// The input is decoded with [`decode_str`](fn.decode_str.html), and the decoded bytes are
+/// encoded with the standard base64 alphabet with padding, as described in RFC 4648.
+///
+/// Failure conditions are exactly the same as those of the [`decode`](fn.decode.html) function.
+///
+/// This function is only available with the `base64` feature.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// assert_eq!(ecoji::ecoji_to_base64("👶😲🇲👅🍉🔙🌥🌩")?, "aW5wdXQgZGF0YQ==");
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn ecoji_to_base64(emoji: &str) -> io::Result<String> {
+    decode_str(emoji).map(|data| STANDARD.encode(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_round_trip() {
+        let data: Vec<u8> = (0..23).map(|i| i * 11).collect();
+        for len in 0..data.len() {
+            let b64 = STANDARD.encode(&data[..len]);
+            let ecoji = base64_to_ecoji(&b64).unwrap();
+            assert_eq!(ecoji, encode_slice_to_string(&data[..len]));
+            assert_eq!(ecoji_to_base64(&ecoji).unwrap(), b64);
+        }
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        let error = base64_to_ecoji("aW5wdXQ").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.get_ref().and_then(|e| e.downcast_ref::<::base64::DecodeError>()).is_some());
+
+        assert_eq!(ecoji_to_base64("👶😲🇲").unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,258 @@
+//! Diagnostic wrappers counting I/O calls.
+//!
+//! Encoding and decoding functions of this crate work with small pieces of data: the encoder
+//! reads and writes one group at a time, and the decoder reads its input one code point at a
+//! time. With unbuffered sources or destinations, like files or sockets, each of these
+//! operations is a system call. The wrappers from this module count the calls made to
+//! the wrapped reader or writer, which helps to find out whether buffering is needed.
+//!
+//! [`SinkWriter`](struct.SinkWriter.html) discards everything written to it and only counts the
+//! bytes, which measures the size of the output without storing it.
+
+use std::io::{self, Read, Write};
+
+/// A reader which counts calls to `read` and bytes read from the wrapped reader.
+///
+/// # Examples
+///
+/// Decoding reads its input one code point at a time, so it should be buffered:
+///
+/// ```
+/// use std::io::BufReader;
+/// use ecoji::CountingReader;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let input = "👶😲🇲👅🍉🔙🌥🌩";
+///
+/// let mut unbuffered = CountingReader::new(input.as_bytes());
+/// ecoji::decode(&mut unbuffered, &mut Vec::new())?;
+/// assert_eq!(unbuffered.calls(), 17);
+///
+/// let mut buffered = BufReader::new(CountingReader::new(input.as_bytes()));
+/// ecoji::decode(&mut buffered, &mut Vec::new())?;
+/// assert_eq!(buffered.get_ref().calls(), 2);
+/// assert_eq!(buffered.get_ref().bytes_read(), input.len() as u64);
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct CountingReader<R> {
+    inner: R,
+    calls: u64,
+    bytes: u64,
+}
+
+impl<R> CountingReader<R> {
+    /// Creates a new reader counting calls to the given reader.
+    pub fn new(inner: R) -> CountingReader<R> {
+        CountingReader { inner, calls: 0, bytes: 0 }
+    }
+
+    /// Returns the number of calls to `read` made so far, including failed ones.
+    pub fn calls(&self) -> u64 {
+        self.calls
+    }
+
+    /// Returns the number of bytes read so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Returns a reference to the wrapped reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps this reader, returning the wrapped reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.calls += 1;
+        let n = self.inner.read(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+}
+
+/// A writer which counts calls to `write` and bytes written to the wrapped writer.
+///
+/// # Examples
+///
+/// Encoding writes each group separately, so its output should be buffered:
+///
+/// ```
+/// use std::io::{BufWriter, Write};
+/// use ecoji::CountingWriter;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let input = "input data";
+///
+/// let mut unbuffered = CountingWriter::new(Vec::new());
+/// ecoji::encode(&mut input.as_bytes(), &mut unbuffered)?;
+/// assert_eq!(unbuffered.calls(), 2);
+///
+/// let mut buffered = BufWriter::new(CountingWriter::new(Vec::new()));
+/// ecoji::encode(&mut input.as_bytes(), &mut buffered)?;
+/// buffered.flush()?;
+/// assert_eq!(buffered.get_ref().calls(), 1);
+/// assert_eq!(buffered.get_ref().bytes_written(), 32);
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct CountingWriter<W> {
+    inner: W,
+    calls: u64,
+    bytes: u64,
+}
+
+impl<W> CountingWriter<W> {
+    /// Creates a new writer counting calls to the given writer.
+    pub fn new(inner: W) -> CountingWriter<W> {
+        CountingWriter { inner, calls: 0, bytes: 0 }
+    }
+
+    /// Returns the number of calls to `write` made so far, including failed ones. Calls to
+    /// `flush` are not counted.
+    pub fn calls(&self) -> u64 {
+        self.calls
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Returns a reference to the wrapped writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Unwraps this writer, returning the wrapped writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.calls += 1;
+        let n = self.inner.write(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A writer which discards all data written to it, counting the number of written bytes.
+///
+/// This measures the size of the encoded or decoded data without storing it, e.g. for sources
+/// whose length is not known in advance; for slices,
+/// [`max_encoded_len`](fn.max_encoded_len.html) gives an upper bound without encoding anything.
+///
+/// # Examples
+///
+/// ```
+/// use ecoji::SinkWriter;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let mut sink = SinkWriter::new();
+/// ecoji::encode(&mut "input data".as_bytes(), &mut sink)?;
+///
+/// assert_eq!(sink.count(), "👶😲🇲👅🍉🔙🌥🌩".len() as u64);
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SinkWriter {
+    bytes: u64,
+}
+
+impl SinkWriter {
+    /// Creates a new writer which has not counted any bytes yet.
+    pub fn new() -> SinkWriter {
+        SinkWriter { bytes: 0 }
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn count(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl Write for SinkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bytes += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counting_encode() {
+        let data: Vec<u8> = (0..23).collect();
+
+        let mut reader = CountingReader::new(data.as_slice());
+        let mut writer = CountingWriter::new(Vec::new());
+        ::encode(&mut reader, &mut writer).unwrap();
+
+        // four full groups, the last partial group and the end of data, which is read only once
+        assert_eq!(reader.calls(), 6);
+        assert_eq!(reader.bytes_read(), 23);
+        assert_eq!(writer.calls(), 5);
+        assert_eq!(writer.bytes_written(), writer.get_ref().len() as u64);
+        assert_eq!(writer.into_inner(), ::encode_to_string(&mut data.as_slice()).unwrap().as_bytes());
+    }
+
+    #[test]
+    fn test_counting_failures() {
+        struct Failing;
+
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> { Err(io::Error::other("failure")) }
+        }
+
+        let mut reader = CountingReader::new(Failing);
+        assert!(::encode(&mut reader, &mut Vec::new()).is_err());
+        assert_eq!(reader.calls(), 1);
+        assert_eq!(reader.bytes_read(), 0);
+    }
+
+    #[test]
+    fn test_sink() {
+        let data: Vec<u8> = (0..23).collect();
+        for len in 0..data.len() {
+            let mut sink = SinkWriter::new();
+            let written = ::encode(&mut &data[..len], &mut sink).unwrap();
+            assert_eq!(sink.count(), written as u64);
+            assert_eq!(sink.count(), ::encode_to_string(&mut &data[..len]).unwrap().len() as u64);
+        }
+    }
+}
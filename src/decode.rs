@@ -1,18 +1,37 @@
-use std::io::{self, Read, Write};
+use std::char;
+use std::cmp;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hasher;
+use std::io::{self, BufRead, Cursor, Read, Write};
+use std::ops::{Deref, Range};
+use std::str;
 
-use chars::{Chars, CharsError};
-use emojis::*;
+use alphabet::{Alphabet, BITS_PER_CHAR, BYTES_PER_GROUP, CHARS_PER_GROUP};
+use alphabet::{MAX_EMOJI_UTF8_LEN, MIN_EMOJI_UTF8_LEN};
+use options::DecodeOptions;
+use chars::{ByteChars, Chars, CharsError, Utf16Chars};
+use error::DecodeError;
 
 /// Decodes the entire source from the Ecoji format (assumed to be UTF-8-encoded) and writes the
 /// result of the decoding to the provided destination.
 ///
+/// Line breaks (`\n` and `\r`) in the source are ignored, so encoded text which was wrapped
+/// into several lines, as done by the reference Ecoji tool, can be decoded directly.
+///
 /// If successful, returns the number of bytes which were written to the destination writer.
 ///
 /// Returns an error when either source or destination operation has failed, if the number of
 /// code points in the input is wrong (it must be a multiple of 4), if the source is not
-/// a valid UTF-8 stream or if one of the code points in the source is not a valid character
-/// of the Ecoji alphabet. No guarantees are made about the state of the destination if an error
-/// occurs, so it is possible for the destination to contain only a part of the decoded data.
+/// a valid UTF-8 stream, if one of the code points in the source is not a valid character
+/// of the Ecoji alphabet or if a padding is in a slot of a group which must hold data, i.e. at
+/// the start of a group, before a data code point or before one of the 4-byte group paddings
+/// ([`DecodeError::MisplacedPadding`](enum.DecodeError.html)). No guarantees are made about
+/// the state of the destination if an error occurs, so it is possible for the destination to
+/// contain only a part of the decoded data.
+/// Errors of the source or the destination keep the kind of the original error and wrap
+/// [`DecodeError::SourceIo`](enum.DecodeError.html) or [`DecodeError::DestIo`](enum.DecodeError.html),
+/// depending on which operation failed.
 ///
 /// # Examples
 ///
@@ -59,10 +78,12 @@ use emojis::*;
 /// }
 /// ```
 ///
-/// Invalid input data, input code point is not a part of the Ecoji alphabet:
+/// Invalid input data, input code point is not a part of the Ecoji alphabet, which is reported
+/// with [`DecodeError::NotInAlphabet`](enum.DecodeError.html):
 ///
 /// ```
 /// use std::io;
+/// use ecoji::DecodeError;
 ///
 /// // Padded with spaces for the length to be a multiple of 4
 /// let input = "Not emoji data  ";
@@ -70,195 +91,2712 @@ use emojis::*;
 /// let mut output: Vec<u8> = Vec::new();
 /// match ecoji::decode(&mut input.as_bytes(), &mut output) {
 ///     Ok(_) => panic!("Unexpected success"),
-///     Err(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+///     Err(e) => {
+///         assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+///         assert_eq!(
+///             e.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+///             Some(&DecodeError::NotInAlphabet { c: 'N', position: 0 })
+///         );
+///         assert_eq!(e.to_string(), "invalid char 'N' (U+004E) at position 0");
+///     }
 /// }
 /// ```
 pub fn decode<R: Read + ?Sized, W: Write + ?Sized>(source: &mut R, destination: &mut W) -> io::Result<usize> {
-    let mut input = Chars::new(source);
+    decode_with(&DecodeOptions::default(), source, destination)
+}
 
-    let mut bytes_written = 0;
-    loop {
-        let mut chars = ['\0'; 4];
+pub(crate) fn decode_with<R: Read + ?Sized, W: Write + ?Sized>(options: &DecodeOptions, source: &mut R,
+                                                              destination: &mut W) -> io::Result<usize> {
+    decode_chars(options, Chars::new(source), destination)
+}
 
-        match input.next() {
-            Some(c) => chars[0] = check_char(c)?,
-            None => break,
-        };
-        for i in 1..4 {
-            match input.next() {
-                Some(c) => chars[i] = check_char(c)?,
-                None => return Err(io::Error::new(
-                    io::ErrorKind::UnexpectedEof,
-                    "Unexpected end of data, input code points count is not a multiple of 4"
-                ))
-            }
-        }
+/// Decodes the entire source from the Ecoji format (assumed to be UTF-8-encoded), writes the
+/// result of the decoding to the provided destination and feeds it to the given hasher.
+///
+/// This makes it possible to compute a checksum of the decoded data without a second pass over
+/// it. Decoded bytes are fed to the hasher exactly as they are written to the destination, in
+/// pieces of up to five bytes, so the hasher must produce the same result regardless of how its
+/// input is split into `write` calls; this is the case for most hashers.
+///
+/// If successful, returns the number of bytes which were written to the destination writer.
+///
+/// Failure conditions are exactly the same as those of the [`decode`](fn.decode.html) function.
+/// If an error occurs, the hasher will have been fed the bytes written before it.
+///
+/// # Examples
+///
+/// ```
+/// use std::hash::Hasher;
+///
+/// /// A 32-bit FNV-1a hasher.
+/// struct Fnv(u64);
+///
+/// impl Hasher for Fnv {
+///     fn write(&mut self, bytes: &[u8]) {
+///         for &b in bytes {
+///             self.0 = ((self.0 ^ b as u64) * 16777619) & 0xffffffff;
+///         }
+///     }
+///
+///     fn finish(&self) -> u64 {
+///         self.0
+///     }
+/// }
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let input = "👶😲🇲👅🍉🔙🌥🌩";
+///
+/// let mut output: Vec<u8> = Vec::new();
+/// let mut hasher = Fnv(2166136261);
+/// ecoji::decode_with_hasher(&mut input.as_bytes(), &mut output, &mut hasher)?;
+///
+/// assert_eq!(output, b"input data");
+/// assert_eq!(hasher.finish(), 0x0e29d3f1);
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn decode_with_hasher<R, W, H>(source: &mut R, destination: &mut W, hasher: &mut H) -> io::Result<usize>
+    where R: Read + ?Sized, W: Write + ?Sized, H: Hasher + ?Sized
+{
+    decode(source, &mut HashingWriter { inner: destination, hasher })
+}
 
-        let (bits1, bits2, bits3) = (
-            EMOJIS_REV.get(&chars[0]).cloned().unwrap_or(0),
-            EMOJIS_REV.get(&chars[1]).cloned().unwrap_or(0),
-            EMOJIS_REV.get(&chars[2]).cloned().unwrap_or(0)
-        );
-        let bits4 = match chars[3] {
-            PADDING_40 => 0,
-            PADDING_41 => 1 << 8,
-            PADDING_42 => 2 << 8,
-            PADDING_43 => 3 << 8,
-            other => EMOJIS_REV.get(&other).cloned().unwrap_or(0),
-        };
+/// Decodes the entire source from the Ecoji format (assumed to be UTF-8-encoded), writes the
+/// result of the decoding to the provided destination and reports the progress to the given
+/// callback.
+///
+/// The callback is invoked with the total number of groups decoded so far after every
+/// `every_n_groups` groups, once the decoded bytes have been written to the destination, and
+/// once more after the last group unless the count was just reported; it is not invoked for
+/// an empty source. Since this happens only at group boundaries, the overhead is negligible,
+/// which makes it suitable e.g. for updating a progress bar while decoding large documents;
+/// the total number of groups can be estimated beforehand from the size of the source.
+///
+/// If successful, returns the number of bytes which were written to the destination writer.
+///
+/// Failure conditions are exactly the same as those of the [`decode`](fn.decode.html) function.
+/// If an error occurs, the callback is not invoked for the groups decoded after the last report.
+///
+/// # Panics
+///
+/// Panics if `every_n_groups` is zero.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let input = ecoji::encode_to_string(&mut [0xa5; 52].as_ref())?;
+///
+/// let mut reports = Vec::new();
+/// let mut output = Vec::new();
+/// ecoji::decode_with_progress(&mut input.as_bytes(), &mut output, 4, |groups| reports.push(groups))?;
+///
+/// assert_eq!(output, [0xa5; 52].as_ref());
+/// assert_eq!(reports, [4, 8, 11]);
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn decode_with_progress<R, W, F>(source: &mut R, destination: &mut W, every_n_groups: usize,
+                                     mut progress: F) -> io::Result<usize>
+    where R: Read + ?Sized, W: Write + ?Sized, F: FnMut(u64)
+{
+    assert!(every_n_groups > 0, "Progress must be reported after at least one group");
 
-        let out = [
-            (bits1 >> 2) as u8,
-            (((bits1 & 0x3) << 6) | (bits2 >> 4)) as u8,
-            (((bits2 & 0xf) << 4) | (bits3 >> 6)) as u8,
-            (((bits3 & 0x3f) << 2) | (bits4 >> 8)) as u8,
-            (bits4 & 0xff) as u8
-        ];
+    let options = DecodeOptions::default();
+    let mut input = Chars::new(source);
+    let mut bytes_written = 0;
+    let mut groups = 0;
+    let mut position = 0;
+    while let Some(group) = decode_group(&options, &mut input, &mut position) {
+        let group = group?;
+        destination.write_all(&group).map_err(DecodeError::wrap_destination)?;
+        bytes_written += group.len();
+        groups += 1;
+        if groups % every_n_groups as u64 == 0 {
+            progress(groups);
+        }
+    }
 
-        let out = if chars[1] == PADDING {
-            &out[..1]
-        } else if chars[2] == PADDING {
-            &out[..2]
-        } else if chars[3] == PADDING {
-            &out[..3]
-        } else if chars[3] == PADDING_40 || chars[3] == PADDING_41 || chars[3] == PADDING_42 || chars[3] == PADDING_43 {
-            &out[..4]
-        } else {
-            &out[..]
-        };
+    if groups % every_n_groups as u64 != 0 {
+        progress(groups);
+    }
+    Ok(bytes_written)
+}
+
+/// A writer which feeds everything written to the wrapped writer to a hasher.
+struct HashingWriter<'a, W: ?Sized + 'a, H: ?Sized + 'a> {
+    inner: &'a mut W,
+    hasher: &'a mut H,
+}
 
-        destination.write_all(out)?;
-        bytes_written += out.len();
+impl<'a, W: Write + ?Sized, H: Hasher + ?Sized> Write for HashingWriter<'a, W, H> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.write(&buf[..n]);
+        Ok(n)
     }
 
-    Ok(bytes_written)
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
 }
 
-/// Decodes the entire source from the Ecoji format (assumed to be UTF-8-encoded), storing the
-/// result of the decoding to a new byte vector.
+/// Decodes data from an iterator over bytes of the Ecoji format (assumed to be UTF-8-encoded)
+/// and writes the result of the decoding to the provided destination.
 ///
-/// Returns a byte vector with the decoded data if successful.
+/// This function is useful for byte sources which do not implement `std::io::Read`, but produce
+/// bytes one by one, possibly failing. Items with errors of the `Interrupted` kind are skipped.
 ///
-/// Failure conditions are exactly the same as those of the [`decode`](fn.decode.html) function.
+/// If successful, returns the number of bytes which were written to the destination writer.
 ///
-/// # Examples
+/// Failure conditions are exactly the same as those of the [`decode`](fn.decode.html) function;
+/// an error yielded by the iterator is treated like a failure to read from the source.
 ///
-/// Successful decoding:
+/// # Examples
 ///
 /// ```
+/// use std::io::Read;
+///
 /// # fn test() -> ::std::io::Result<()> {
 /// let input = "👶😲🇲👅🍉🔙🌥🌩";
-/// let output: Vec<u8> = ecoji::decode_to_vec(&mut input.as_bytes())?;
+///
+/// let mut output: Vec<u8> = Vec::new();
+/// ecoji::decode_from_byte_iter(input.as_bytes().bytes(), &mut output)?;
 ///
 /// assert_eq!(output, b"input data");
 /// #  Ok(())
 /// # }
 /// # test().unwrap();
 /// ```
-///
-/// See [`decode`](fn.decode.html) docs for error examples.
-pub fn decode_to_vec<R: Read + ?Sized>(source: &mut R) -> io::Result<Vec<u8>> {
-    let mut output = Vec::new();
-    decode(source, &mut output)?;
-    Ok(output)
+pub fn decode_from_byte_iter<I, W>(iter: I, destination: &mut W) -> io::Result<usize>
+    where I: Iterator<Item = io::Result<u8>>, W: Write + ?Sized
+{
+    decode_chars(&DecodeOptions::default(), ByteChars::new(iter), destination)
 }
 
-/// Decodes the entire source from the Ecoji format (assumed to be UTF-8-encoded), storing the
-/// result of the decoding to a new owned string.
+/// Decodes data from UTF-16 code units of the Ecoji format and writes the result of the decoding to
+/// the provided destination.
 ///
-/// Returns a string with the decoded data if successful.
+/// This function is useful for UTF-16 sources, like strings coming from Windows APIs or other
+/// platforms using UTF-16, which do not have to be transcoded into UTF-8 before decoding. Code
+/// points outside of the Basic Multilingual Plane, which includes most of the alphabet, are
+/// represented by surrogate pairs.
 ///
-/// In addition to the [`decode`](fn.decode.html) failure conditions, this function also returns
-/// an error if the decoded data is not a valid UTF-8 string.
+/// If successful, returns the number of bytes which were written to the destination writer.
 ///
-/// # Examples
+/// Failure conditions are exactly the same as those of the [`decode`](fn.decode.html) function,
+/// except that instead of invalid UTF-8 data, an error of the `InvalidData` kind is returned if
+/// the input contains a surrogate code unit without a pair.
 ///
-/// Successful decoding:
+/// # Examples
 ///
 /// ```
 /// # fn test() -> ::std::io::Result<()> {
-/// let input = "👶😲🇲👅🍉🔙🌥🌩";
-/// let output: String = ecoji::decode_to_string(&mut input.as_bytes())?;
+/// let input: Vec<u16> = "👶😲🇲👅🍉🔙🌥🌩".encode_utf16().collect();
 ///
-/// assert_eq!(output, "input data");
+/// let mut output: Vec<u8> = Vec::new();
+/// ecoji::decode_utf16(&input, &mut output)?;
+/// assert_eq!(output, b"input data");
+///
+/// let error = ecoji::decode_utf16(&input[1..], &mut Vec::new()).unwrap_err();
+/// assert_eq!(error.to_string(), "utf16 data contained an unpaired surrogate 0xdc76 at offset 0");
 /// #  Ok(())
 /// # }
 /// # test().unwrap();
 /// ```
+pub fn decode_utf16<W: Write + ?Sized>(units: &[u16], destination: &mut W) -> io::Result<usize> {
+    decode_chars(&DecodeOptions::default(), Utf16Chars::new(units.iter().cloned()), destination)
+}
+
+/// Decodes data from numeric Unicode code points of the Ecoji format and writes the result of the
+/// decoding to the provided destination.
 ///
-/// Invalid input data, decoded string is not a valid UTF-8 string:
+/// This function is useful when encoded data was stored as numbers somewhere, e.g. in a JSON array,
+/// instead of as text.
+///
+/// If successful, returns the number of bytes which were written to the destination writer.
+///
+/// Failure conditions are exactly the same as those of the [`decode`](fn.decode.html) function,
+/// except that instead of invalid UTF-8 data, an error of the `InvalidData` kind is returned if
+/// the input contains a number which is not a Unicode scalar value, i.e. a surrogate or a number
+/// above `0x10FFFF`.
+///
+/// # Examples
 ///
 /// ```
-/// use std::io;
+/// # fn test() -> ::std::io::Result<()> {
+/// let input = [128118, 128562, 127474, 128069, 127817, 128281, 127781, 127785];
 ///
-/// let input = "🧑🦲🧕🙋";  // Encoded data: [0xfe, 0xfe, 0xff, 0xff]
+/// let mut output: Vec<u8> = Vec::new();
+/// ecoji::decode_codepoints(&input, &mut output)?;
+/// assert_eq!(output, b"input data");
 ///
-/// match ecoji::decode_to_string(&mut input.as_bytes()) {
-///     Ok(_) => panic!("Unexpected success"),
-///     Err(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
-/// }
+/// let error = ecoji::decode_codepoints(&[0xD83D, 0xDC76, 0x1F632, 0x1F1F2], &mut Vec::new()).unwrap_err();
+/// assert_eq!(error.to_string(), "code point 0xd83d at offset 0 is not a valid unicode scalar value");
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
 /// ```
-pub fn decode_to_string<R: Read + ?Sized>(source: &mut R) -> io::Result<String> {
-    let output = decode_to_vec(source)?;
-    String::from_utf8(output).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+pub fn decode_codepoints<W: Write + ?Sized>(points: &[u32], destination: &mut W) -> io::Result<usize> {
+    let chars = points.iter().enumerate().map(|(i, &value)| {
+        char::from_u32(value).ok_or(CharsError::InvalidCodePoint { offset: i as u64, value })
+    });
+    decode_chars(&DecodeOptions::default(), chars, destination)
 }
 
-fn check_char(c: Result<char, CharsError>) -> io::Result<char> {
-    c.map_err(CharsError::into_io).and_then(|c| if is_valid_alphabet_char(c) {
-        Ok(c)
-    } else {
-        Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!("Input character '{}' is not a part of the Ecoji alphabet", c)
-        ))
-    })
+/// Decodes data from indices of code points in the alphabet, as returned by
+/// [`encode_to_indices`](fn.encode_to_indices.html), storing the result of the decoding to a new
+/// byte vector.
+///
+/// Failure conditions are the same as those of the [`decode`](fn.decode.html) function, except
+/// that instead of code points which are not in the alphabet, an error of the `InvalidData` kind
+/// wrapping [`DecodeError::InvalidIndex`](enum.DecodeError.html) is returned for indices above
+/// 1028.
+///
+/// # Examples
+///
+/// ```
+/// use ecoji::DecodeError;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// assert_eq!(ecoji::decode_from_indices(&[421, 743, 29, 372, 129, 582, 93, 97])?, b"input data");
+///
+/// let error = ecoji::decode_from_indices(&[421, 743, 29, 2000]).unwrap_err();
+/// assert_eq!(
+///     error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+///     Some(&DecodeError::InvalidIndex { index: 2000, position: 3 })
+/// );
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn decode_from_indices(indices: &[u16]) -> io::Result<Vec<u8>> {
+    let alphabet = &Alphabet::STANDARD;
+    let chars = indices.iter().enumerate()
+        .map(|(position, &index)| alphabet.char_at(index as usize)
+            .ok_or_else(|| DecodeError::InvalidIndex { index, position: position as u64 }.into_io()))
+        .collect::<io::Result<Vec<char>>>()?;
+
+    let mut output = Vec::with_capacity(indices.len() / CHARS_PER_GROUP * BYTES_PER_GROUP);
+    decode_chars(&DecodeOptions::default(), chars.into_iter().map(Ok), &mut output)?;
+    Ok(output)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Checks whether two Ecoji-encoded strings represent the same data, ignoring whitespace.
+///
+/// Both strings are decoded, skipping any whitespace (such as line breaks inserted when wrapping
+/// encoded text), and the decoded data is compared. Therefore, strings which were wrapped
+/// differently, or concatenations of encoded strings, compare equal to each other if they
+/// decode to the same bytes.
+///
+/// Returns an error if either string is not valid Ecoji data, with the same failure conditions
+/// as those of the [`decode`](fn.decode.html) function.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// assert!(ecoji::ecoji_eq("👶😲🇲👅🍉🔙🌥🌩", "👶😲🇲👅\n🍉🔙🌥🌩\n")?);
+/// assert!(!ecoji::ecoji_eq("👶😲🇲👅🍉🔙🌥🌩", "👖📸🎈☕")?);
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn ecoji_eq(a: &str, b: &str) -> io::Result<bool> {
+    let decode_skipping_whitespace = |s: &str| {
+        let mut output = Vec::new();
+        let input = s.chars().filter(|c| !c.is_whitespace()).map(Ok);
+        decode_chars(&DecodeOptions::default(), input, &mut output).map(|_| output)
+    };
 
-    fn check(input: &[u8], output: &[u8]) {
-        let buf = decode_to_vec(&mut input.clone()).unwrap();
-        assert_eq!(output, buf.as_slice());
+    Ok(decode_skipping_whitespace(a)? == decode_skipping_whitespace(b)?)
+}
+
+fn decode_chars<I, W>(options: &DecodeOptions, mut input: I, destination: &mut W) -> io::Result<usize>
+    where I: Iterator<Item = Result<char, CharsError>>, W: Write + ?Sized
+{
+    let mut bytes_written = 0;
+    let mut position = 0;
+    while let Some(group) = decode_group(options, &mut input, &mut position) {
+        let group = group?;
+        destination.write_all(&group).map_err(DecodeError::wrap_destination)?;
+        bytes_written += group.len();
     }
 
-    fn check_chars(input: &[char], output: &[u8]) {
-        let input: String = input.iter().cloned().collect();
-        let buf = decode_to_vec(&mut input.as_bytes()).unwrap();
-        assert_eq!(output, buf.as_slice());
+    Ok(bytes_written)
+}
+
+/// Bytes obtained by decoding a single group of four Ecoji code points.
+///
+/// A group decodes to five bytes, except for the last group of a stream, which may decode to
+/// fewer bytes depending on its padding. This type dereferences to a byte slice of the
+/// actual decoded length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodedGroup {
+    bytes: [u8; BYTES_PER_GROUP],
+    len: usize,
+}
+
+impl Deref for DecodedGroup {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes[..self.len]
     }
+}
 
-    #[test]
-    fn test_random() {
-        check("👖📸🎈☕".as_bytes(), b"abc");
+impl AsRef<[u8]> for DecodedGroup {
+    fn as_ref(&self) -> &[u8] {
+        self
     }
+}
 
-    #[test]
-    fn test_one_byte() {
-        check_chars(&[EMOJIS[('k' as usize) << 2], PADDING, PADDING, PADDING], b"k");
+/// An iterator over decoded groups of an Ecoji stream.
+///
+/// This struct is created by the [`decode_groups`](fn.decode_groups.html) function. See its
+/// documentation for more details.
+#[derive(Debug, Clone)]
+pub struct DecodeGroups<R> {
+    options: DecodeOptions,
+    input: Chars<R>,
+    position: u64,
+    done: bool,
+}
+
+impl<R: Read> Iterator for DecodeGroups<R> {
+    type Item = io::Result<DecodedGroup>;
+
+    fn next(&mut self) -> Option<io::Result<DecodedGroup>> {
+        if self.done {
+            return None;
+        }
+
+        let result = decode_group(&self.options, &mut self.input, &mut self.position);
+        match result {
+            Some(Ok(_)) => {}
+            _ => self.done = true,
+        }
+        result
     }
+}
 
-    #[test]
-    fn test_two_bytes() {
-        check_chars(&[EMOJIS[0], EMOJIS[16], PADDING, PADDING], &[0, 1]);
+/// Returns an iterator which lazily decodes the source from the Ecoji format (assumed to be
+/// UTF-8-encoded), yielding the bytes of each group as soon as its four code points are read.
+///
+/// Each item contains five bytes, except for the last one, which may contain fewer bytes
+/// depending on the padding of the final group.
+///
+/// Failure conditions are exactly the same as those of the [`decode`](fn.decode.html) function.
+/// An error is yielded as the last item of the iterator; no further items are produced after it.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let input = "👶😲🇲👅🍉🔙🌥🌩";
+///
+/// let groups: Vec<_> = ecoji::decode_groups(input.as_bytes()).collect::<Result<_, _>>()?;
+///
+/// assert_eq!(groups.len(), 2);
+/// assert_eq!(&*groups[0], b"input");
+/// assert_eq!(&*groups[1], b" data");
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn decode_groups<R: Read>(source: R) -> DecodeGroups<R> {
+    decode_groups_with(&DecodeOptions::default(), source)
+}
+
+pub(crate) fn decode_groups_with<R: Read>(options: &DecodeOptions, source: R) -> DecodeGroups<R> {
+    DecodeGroups {
+        options: options.clone(),
+        input: Chars::new(source),
+        position: 0,
+        done: false,
     }
+}
 
-    #[test]
-    fn test_three_bytes() {
-        check_chars(&[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING], &[0, 1, 2]);
+/// An iterator which lazily decodes an Ecoji-encoded string, yielding one decoded byte at a
+/// time.
+///
+/// Unlike [`decode_to_vec`](fn.decode_to_vec.html), nothing is allocated: only the bytes of the
+/// current group are kept. This makes it cheap to check a prefix of the decoded data, e.g. a magic
+/// number, without decoding all of it.
+///
+/// Failure conditions are exactly the same as those of the [`decode`](fn.decode.html) function.
+/// Groups are decoded as they are reached, so bytes of the preceding groups are yielded before
+/// an error. An error is yielded as the last item of the iterator; no further items are produced
+/// after it.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let input = ecoji::encode_to_string(&mut &b"\x89PNG and more data"[..])?;
+///
+/// let magic = ecoji::DecodedBytes::new(&input).take(4).collect::<Result<Vec<u8>, _>>()?;
+/// assert_eq!(magic, b"\x89PNG");
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct DecodedBytes<'a> {
+    options: DecodeOptions,
+    input: str::Chars<'a>,
+    position: u64,
+    group: DecodedGroup,
+    pos: usize,
+    done: bool,
+}
+
+impl<'a> DecodedBytes<'a> {
+    /// Creates a new iterator over the decoded bytes of the given string.
+    pub fn new(input: &'a str) -> DecodedBytes<'a> {
+        DecodedBytes {
+            options: DecodeOptions::default(),
+            input: input.chars(),
+            position: 0,
+            group: DecodedGroup { bytes: [0; BYTES_PER_GROUP], len: 0 },
+            pos: 0,
+            done: false,
+        }
     }
+}
 
-    #[test]
-    fn test_four_bytes() {
-        check_chars(&[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING_40], &[0, 1, 2, 0]);
-        check_chars(&[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING_41], &[0, 1, 2, 1]);
-        check_chars(&[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING_42], &[0, 1, 2, 2]);
-        check_chars(&[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING_43], &[0, 1, 2, 3]);
+impl<'a> Iterator for DecodedBytes<'a> {
+    type Item = io::Result<u8>;
+
+    fn next(&mut self) -> Option<io::Result<u8>> {
+        if self.pos == self.group.len() {
+            if self.done {
+                return None;
+            }
+            match decode_group(&self.options, &mut self.input.by_ref().map(Ok), &mut self.position) {
+                Some(Ok(group)) => self.group = group,
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+                None => {
+                    self.done = true;
+                    return None;
+                }
+            }
+            self.pos = 0;
+        }
+
+        let b = self.group[self.pos];
+        self.pos += 1;
+        Some(Ok(b))
     }
+}
 
-    #[test]
-    fn test_five_bytes() {
-        check_chars(&[EMOJIS[687], EMOJIS[222], EMOJIS[960], EMOJIS[291]], &[0xAB, 0xCD, 0xEF, 0x01, 0x23]);
+/// A reader which decodes data read from the wrapped reader from the Ecoji format (assumed to be
+/// UTF-8-encoded).
+///
+/// Data is decoded group by group, as it is read from the decoder, so decoding errors are only
+/// reported when the respective group is reached. The possible errors are exactly the same as
+/// the failure conditions of the [`decode`](fn.decode.html) function.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Read;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let input = "👶😲🇲👅🍉🔙🌥🌩";
+///
+/// let mut output = String::new();
+/// ecoji::Decoder::new(input.as_bytes()).read_to_string(&mut output)?;
+///
+/// assert_eq!(output, "input data");
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+///
+/// The decoder also implements `BufRead`, so decoded text can be processed line by line:
+///
+/// ```
+/// use std::io::BufRead;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let input = ecoji::encode_to_string(&mut "first line\nsecond line\n".as_bytes())?;
+///
+/// let lines: Vec<String> = ecoji::Decoder::new(input.as_bytes()).lines().collect::<Result<_, _>>()?;
+///
+/// assert_eq!(lines, ["first line", "second line"]);
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+#[derive(Clone)]
+pub struct Decoder<R> {
+    groups: DecodeGroups<R>,
+    group: DecodedGroup,
+    pos: usize,
+}
+
+impl<R: Read> Decoder<R> {
+    /// Creates a new decoder reading the encoded data from the given reader.
+    pub fn new(inner: R) -> Decoder<R> {
+        Decoder::with_options(&DecodeOptions::default(), inner)
+    }
+
+    pub(crate) fn with_options(options: &DecodeOptions, inner: R) -> Decoder<R> {
+        Decoder {
+            groups: decode_groups_with(options, inner),
+            group: DecodedGroup { bytes: [0; BYTES_PER_GROUP], len: 0 },
+            pos: 0,
+        }
+    }
+
+    /// Returns a reference to the wrapped reader.
+    pub fn get_ref(&self) -> &R {
+        self.groups.input.get_ref()
+    }
+
+    /// Returns a mutable reference to the wrapped reader.
+    ///
+    /// Reading from the wrapped reader directly may leave the decoder in the middle of a group,
+    /// which will likely cause decoding errors.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.groups.input.get_mut()
+    }
+
+    /// Resets the decoder so that it can decode the next message from the wrapped reader.
+    ///
+    /// Once the wrapped reader reports the end of a message or a decoding error occurs, the
+    /// decoder stops reading; after a reset, it starts decoding again from the current position
+    /// of the wrapped reader, and offsets in errors are counted from there. This makes it possible
+    /// to decode several independent messages coming from one reader, e.g. a connection wrapped in
+    /// a reader which ends at message boundaries, with the same decoder.
+    ///
+    /// Decoded bytes which have not been read yet are discarded, so this method must only be
+    /// called at a group boundary, that is, after the previous message has been read to the end.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Read;
+    ///
+    /// # fn test() -> ::std::io::Result<()> {
+    /// let input = "👖📸🎈☕👶😲🇲👅🍉🔙🌥🌩";
+    /// let first_len = "👖📸🎈☕".len() as u64;
+    ///
+    /// let mut decoder = ecoji::Decoder::new(input.as_bytes().take(first_len));
+    /// let mut output = String::new();
+    /// decoder.read_to_string(&mut output)?;
+    /// assert_eq!(output, "abc");
+    ///
+    /// decoder.get_mut().set_limit(u64::MAX);
+    /// decoder.reset();
+    /// output.clear();
+    /// decoder.read_to_string(&mut output)?;
+    /// assert_eq!(output, "input data");
+    /// #  Ok(())
+    /// # }
+    /// # test().unwrap();
+    /// ```
+    pub fn reset(&mut self) {
+        self.groups.done = false;
+        self.groups.input.reset_offset();
+        self.groups.position = 0;
+        self.group.len = 0;
+        self.pos = 0;
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let n = {
+            let available = self.fill_buf()?;
+            let n = cmp::min(buf.len(), available.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+/// The buffer of a decoder holds the decoded bytes of the current group, so `fill_buf` returns
+/// at most five bytes at a time.
+impl<R: Read> BufRead for Decoder<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.group.len() {
+            match self.groups.next() {
+                Some(group) => self.group = group?,
+                None => return Ok(&[]),
+            }
+            self.pos = 0;
+        }
+
+        Ok(&self.group[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = cmp::min(self.pos + amt, self.group.len());
+    }
+}
+
+impl<R: fmt::Debug> fmt::Debug for Decoder<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Decoder")
+            .field("inner", self.groups.input.get_ref())
+            .field("alphabet", &self.groups.options.alphabet)
+            .field("buffered", &(self.group.len() - self.pos))
+            .finish()
+    }
+}
+
+/// Decodes the next group of the input; `position` is the number of code points consumed so far,
+/// which is used for error reporting.
+fn decode_group<I>(options: &DecodeOptions, input: &mut I, position: &mut u64) -> Option<io::Result<DecodedGroup>>
+    where I: Iterator<Item = Result<char, CharsError>>
+{
+    let alphabet = &options.alphabet;
+    let mut chars = ['\0'; CHARS_PER_GROUP];
+    let mut positions = [0; CHARS_PER_GROUP];
+
+    match next_char(input, options, position) {
+        Some(c) => match check_char(alphabet, c, *position - 1) {
+            Ok(c) => {
+                chars[0] = c;
+                positions[0] = *position - 1;
+            }
+            Err(e) => return Some(Err(e)),
+        },
+        None => return None,
+    };
+    for i in 1..CHARS_PER_GROUP {
+        match next_char(input, options, position) {
+            Some(c) => match check_char(alphabet, c, *position - 1) {
+                Ok(c) => {
+                    chars[i] = c;
+                    positions[i] = *position - 1;
+                }
+                Err(e) => return Some(Err(e)),
+            },
+            None if options.pad_incomplete => {
+                for slot in &mut chars[i..] {
+                    *slot = alphabet.padding();
+                }
+                break;
+            }
+            None => return Some(Err(incomplete_group_error())),
+        }
+    }
+
+    if let Some(delimiter) = options.delimiter {
+        match next_char_or_delimiter(input, options, delimiter, position) {
+            None => {}
+            Some(Ok(c)) if c == delimiter => {}
+            Some(Ok(c)) => return Some(Err(DecodeError::MissingDelimiter { delimiter, c, position: *position - 1 }.into_io())),
+            Some(Err(e)) => return Some(Err(e.into_io())),
+        }
+    }
+
+    if let Some(i) = misplaced_padding(alphabet, &chars) {
+        return Some(Err(DecodeError::MisplacedPadding { c: chars[i], position: positions[i] }.into_io()));
+    }
+
+    let (bits1, bits2, bits3) = (
+        alphabet.bits(chars[0]).unwrap_or(0),
+        alphabet.bits(chars[1]).unwrap_or(0),
+        alphabet.bits(chars[2]).unwrap_or(0)
+    );
+    let bits4 = match alphabet.padding_4x_bits(chars[3]) {
+        Some(low_bits) => low_bits << 8,
+        None => alphabet.bits(chars[3]).unwrap_or(0),
+    };
+
+    let bytes = [
+        (bits1 >> 2) as u8,
+        (((bits1 & 0x3) << 6) | (bits2 >> 4)) as u8,
+        (((bits2 & 0xf) << 4) | (bits3 >> 6)) as u8,
+        (((bits3 & 0x3f) << 2) | (bits4 >> 8)) as u8,
+        (bits4 & 0xff) as u8
+    ];
+
+    Some(Ok(DecodedGroup { bytes, len: group_len(alphabet, &chars) }))
+}
+
+/// Returns the slot of the first padding in the group which is in a slot that must hold data.
+///
+/// A valid group consists of at least one data code point followed only by paddings, of which
+/// only the last one may be one of the 4-byte group paddings, and only after three data code
+/// points. Lookups of paddings in the alphabet fail, so without this check they would silently
+/// decode as zero bits.
+fn misplaced_padding(alphabet: &Alphabet, chars: &[char; CHARS_PER_GROUP]) -> Option<usize> {
+    let is_padding = |c: char| c == alphabet.padding() || alphabet.padding_4x_bits(c).is_some();
+
+    // A group ending with one of the 4-byte group paddings carries 30 bits of data in its first
+    // three code points, so none of them may be a padding
+    if alphabet.padding_4x_bits(chars[3]).is_some() {
+        return chars[..3].iter().position(|&c| is_padding(c));
+    }
+
+    let data_len = chars.iter().take_while(|&&c| !is_padding(c)).count();
+    if data_len == 0 {
+        return Some(0);
+    }
+    // After the first padding, a data code point means that the padding itself was in a data
+    // slot, while a 4-byte group padding is misplaced on its own
+    chars[data_len..].iter()
+        .position(|&c| c != alphabet.padding())
+        .map(|i| if is_padding(chars[data_len + i]) { data_len + i } else { data_len })
+}
+
+/// Returns the next character of the input, skipping line breaks and the characters ignored by
+/// the options, and advances the position past it.
+fn next_char<I>(input: &mut I, options: &DecodeOptions, position: &mut u64) -> Option<Result<char, CharsError>>
+    where I: Iterator<Item = Result<char, CharsError>>
+{
+    input.find(|c| {
+        *position += 1;
+        match *c {
+            Ok(c) => !is_skipped(options, c),
+            Err(_) => true,
+        }
+    })
+}
+
+/// Returns the next character of the input like `next_char`, except that the given delimiter is
+/// returned even if it would be skipped, e.g. when it is a line break.
+fn next_char_or_delimiter<I>(input: &mut I, options: &DecodeOptions, delimiter: char,
+                             position: &mut u64) -> Option<Result<char, CharsError>>
+    where I: Iterator<Item = Result<char, CharsError>>
+{
+    input.find(|c| {
+        *position += 1;
+        match *c {
+            Ok(c) => c == delimiter || !is_skipped(options, c),
+            Err(_) => true,
+        }
+    })
+}
+
+/// Checks whether the character is skipped when decoding with the options.
+fn is_skipped(options: &DecodeOptions, c: char) -> bool {
+    c == '\n' || c == '\r' || options.ignore_chars.contains(&c) || is_stripped_skin_tone(options, c)
+}
+
+/// Checks whether the character is a skin tone modifier which is skipped with the options.
+fn is_stripped_skin_tone(options: &DecodeOptions, c: char) -> bool {
+    options.strip_skin_tones && ('\u{1F3FB}'..='\u{1F3FF}').contains(&c) && !options.alphabet.contains(c)
+}
+
+fn incomplete_group_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "Unexpected end of data, input code points count is not a multiple of 4"
+    )
+}
+
+/// Returns the number of bytes a group of code points decodes to, based on its padding.
+fn group_len(alphabet: &Alphabet, chars: &[char; CHARS_PER_GROUP]) -> usize {
+    if chars[1] == alphabet.padding() {
+        1
+    } else if chars[2] == alphabet.padding() {
+        2
+    } else if chars[3] == alphabet.padding() {
+        3
+    } else if alphabet.padding_4x_bits(chars[3]).is_some() {
+        4
+    } else {
+        BYTES_PER_GROUP
+    }
+}
+
+/// Decodes the entire source from the Ecoji format (assumed to be UTF-8-encoded), storing the
+/// result of the decoding to a new byte vector.
+///
+/// Returns a byte vector with the decoded data if successful.
+///
+/// Failure conditions are exactly the same as those of the [`decode`](fn.decode.html) function.
+///
+/// # Examples
+///
+/// Successful decoding:
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let input = "👶😲🇲👅🍉🔙🌥🌩";
+/// let output: Vec<u8> = ecoji::decode_to_vec(&mut input.as_bytes())?;
+///
+/// assert_eq!(output, b"input data");
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+///
+/// See [`decode`](fn.decode.html) docs for error examples.
+pub fn decode_to_vec<R: Read + ?Sized>(source: &mut R) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    decode(source, &mut output)?;
+    Ok(output)
+}
+
+/// Decodes the rest of the cursor's data from the Ecoji format (assumed to be UTF-8-encoded),
+/// from its current position to the end, and returns a new cursor over the decoded data.
+///
+/// This is the counterpart of [`encode_cursor`](fn.encode_cursor.html), see its documentation
+/// for an example. The source cursor is advanced to the end of its data, and the returned cursor
+/// is positioned at the end of the decoded data, so further writes to it append to the output.
+///
+/// Failure conditions are exactly the same as those of the [`decode`](fn.decode.html) function.
+/// If an error occurs, the source cursor is left somewhere after the last decoded group.
+pub fn decode_cursor<T: AsRef<[u8]>>(source: &mut Cursor<T>) -> io::Result<Cursor<Vec<u8>>> {
+    let mut output = Cursor::new(Vec::new());
+    decode(source, &mut output)?;
+    Ok(output)
+}
+
+/// Decodes the entire source from the Ecoji format (assumed to be UTF-8-encoded), storing the
+/// result of the decoding to a new byte vector which is not allowed to grow beyond `max_bytes`.
+///
+/// This function is meant for decoding untrusted input: decoding stops as soon as the decoded
+/// data would exceed `max_bytes` bytes, without reading the rest of the source.
+///
+/// Returns a byte vector with the decoded data if successful.
+///
+/// In addition to the [`decode`](fn.decode.html) failure conditions, this function also returns
+/// an error of the `InvalidData` kind wrapping
+/// [`DecodeError::OutputTooLarge`](enum.DecodeError.html) if the limit is exceeded.
+///
+/// # Examples
+///
+/// ```
+/// use ecoji::DecodeError;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let input = "👶😲🇲👅🍉🔙🌥🌩";
+///
+/// let output = ecoji::decode_to_vec_limited(&mut input.as_bytes(), 10)?;
+/// assert_eq!(output, b"input data");
+///
+/// let error = ecoji::decode_to_vec_limited(&mut input.as_bytes(), 9).unwrap_err();
+/// assert_eq!(
+///     error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+///     Some(&DecodeError::OutputTooLarge { limit: 9 })
+/// );
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn decode_to_vec_limited<R: Read + ?Sized>(source: &mut R, max_bytes: usize) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    for group in decode_groups(source) {
+        let group = group?;
+        if output.len() + group.len() > max_bytes {
+            return Err(DecodeError::OutputTooLarge { limit: max_bytes }.into_io());
+        }
+        output.extend_from_slice(&group);
+    }
+    Ok(output)
+}
+
+/// Decodes data from the source in the Ecoji format (assumed to be UTF-8-encoded) and writes the
+/// result of the decoding to the provided destination, stopping as soon as `max_output_bytes`
+/// bytes have been written.
+///
+/// This is meant for reading just the beginning of a large stream, e.g. a header. Only the groups
+/// needed to produce the requested number of bytes are read from the source, and the rest of it
+/// is left unread. Since a group decodes to up to five bytes, only a part of the last group read
+/// may be written to the destination.
+///
+/// If successful, returns the number of bytes which were written to the destination writer and
+/// whether the limit was reached. The limit is not reached if the source ends before
+/// `max_output_bytes` bytes could be decoded; the source may or may not contain more data if it
+/// was reached.
+///
+/// Failure conditions are exactly the same as those of the [`decode`](fn.decode.html) function,
+/// but only apply to the part of the source which is actually read.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let mut input = "👶😲🇲👅🍉🔙🌥🌩".as_bytes();
+///
+/// let mut output: Vec<u8> = Vec::new();
+/// assert_eq!(ecoji::decode_take(&mut input, &mut output, 3)?, (3, true));
+/// assert_eq!(output, b"inp");
+///
+/// // The second group has not been read
+/// assert_eq!(input, "🍉🔙🌥🌩".as_bytes());
+///
+/// let mut output: Vec<u8> = Vec::new();
+/// assert_eq!(ecoji::decode_take(&mut input, &mut output, 100)?, (5, false));
+/// assert_eq!(output, b" data");
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn decode_take<R: Read + ?Sized, W: Write + ?Sized>(source: &mut R, destination: &mut W,
+                                                       max_output_bytes: usize) -> io::Result<(usize, bool)> {
+    let options = DecodeOptions::default();
+    let mut input = Chars::new(source);
+    let mut position = 0;
+    let mut bytes_written = 0;
+
+    while bytes_written < max_output_bytes {
+        let group = match decode_group(&options, &mut input, &mut position) {
+            Some(group) => group?,
+            None => return Ok((bytes_written, false)),
+        };
+        let n = cmp::min(group.len(), max_output_bytes - bytes_written);
+        destination.write_all(&group[..n]).map_err(DecodeError::wrap_destination)?;
+        bytes_written += n;
+    }
+
+    Ok((bytes_written, true))
+}
+
+/// Decodes consecutive segments of the given numbers of groups from the source in the Ecoji
+/// format (assumed to be UTF-8-encoded), storing the result of decoding each segment to a
+/// separate byte vector.
+///
+/// Because Ecoji streams can be concatenated, several records may be stored back to back with
+/// no delimiter. Record boundaries cannot be recovered from the stream itself, so the number of
+/// groups (four code points each) of every record must be known in advance, for example from a
+/// length prefix stored elsewhere. Only the requested groups are read from the source; any
+/// remaining data is left unread.
+///
+/// Returns a vector of decoded segments, one for each element of `group_counts`, if successful.
+///
+/// In addition to the [`decode`](fn.decode.html) failure conditions, this function returns an
+/// error of the `UnexpectedEof` kind if the source contains fewer groups than requested.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let records = ecoji::encode_to_string(&mut "first".as_bytes())?
+///     + &ecoji::encode_to_string(&mut "second".as_bytes())?;
+///
+/// let segments = ecoji::decode_fixed_segments(&mut records.as_bytes(), &[1, 2])?;
+///
+/// assert_eq!(segments, [&b"first"[..], &b"second"[..]]);
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn decode_fixed_segments<R: Read + ?Sized>(source: &mut R, group_counts: &[usize]) -> io::Result<Vec<Vec<u8>>> {
+    let mut groups = decode_groups(source);
+    group_counts.iter()
+        .map(|&count| {
+            let mut segment = Vec::with_capacity(count * BYTES_PER_GROUP);
+            for _ in 0..count {
+                match groups.next() {
+                    Some(group) => segment.extend_from_slice(&group?),
+                    None => return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Unexpected end of data, input contains fewer groups than expected"
+                    )),
+                }
+            }
+            Ok(segment)
+        })
+        .collect()
+}
+
+/// Decodes an Ecoji-encoded string, storing the result of the decoding to a new byte vector.
+///
+/// The vector is allocated upfront with the exact capacity required for the decoded data,
+/// as computed by [`decoded_len`](fn.decoded_len.html), so no reallocations happen during
+/// decoding. In particular, nothing is allocated at all if the input is empty.
+///
+/// Failure conditions are exactly the same as those of the [`decode`](fn.decode.html) function.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let output: Vec<u8> = ecoji::decode_str("👶😲🇲👅🍉🔙🌥🌩")?;
+///
+/// assert_eq!(output, b"input data");
+/// assert_eq!(output.capacity(), 10);
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn decode_str(input: &str) -> io::Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(decoded_len(input));
+    decode(&mut input.as_bytes(), &mut output)?;
+    debug_assert_eq!(output.len(), decoded_len(input), "decoded length differs from the predicted one");
+    Ok(output)
+}
+
+/// Decodes an Ecoji-encoded string into the given slice, requiring the decoded data to fill it
+/// exactly.
+///
+/// This is meant for fixed-size buffers, e.g. when the size of the data is known from
+/// a protocol, and any deviation from it indicates corruption. Nothing is allocated.
+///
+/// In addition to the [`decode`](fn.decode.html) failure conditions, returns an error of the
+/// `InvalidData` kind wrapping [`DecodeError::LengthMismatch`](enum.DecodeError.html) if the
+/// decoded data is shorter or longer than the slice; the whole input is decoded to report its
+/// actual length. If an error occurs, the slice may contain a part of the decoded data.
+///
+/// # Examples
+///
+/// ```
+/// use ecoji::DecodeError;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let mut output = [0; 10];
+/// ecoji::decode_exact("👶😲🇲👅🍉🔙🌥🌩", &mut output)?;
+/// assert_eq!(&output, b"input data");
+///
+/// let error = ecoji::decode_exact("👶😲🇲👅🍉🔙🌥🌩", &mut [0; 16]).unwrap_err();
+/// assert_eq!(
+///     error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+///     Some(&DecodeError::LengthMismatch { expected: 16, actual: 10 })
+/// );
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn decode_exact(input: &str, out: &mut [u8]) -> io::Result<()> {
+    let options = DecodeOptions::default();
+    let mut chars = input.chars().map(Ok);
+    let mut position = 0;
+    let mut len = 0;
+    while let Some(group) = decode_group(&options, &mut chars, &mut position) {
+        let group = group?;
+        if let Some(dest) = out.get_mut(len..len + group.len()) {
+            dest.copy_from_slice(&group);
+        }
+        len += group.len();
+    }
+
+    if len != out.len() {
+        return Err(DecodeError::LengthMismatch { expected: out.len(), actual: len }.into_io());
+    }
+    Ok(())
+}
+
+/// Decodes an Ecoji-encoded string which may be missing its last code point.
+///
+/// This is a recovery helper for data truncated by exactly one code point, e.g. by a tool which
+/// dropped the final character: if the number of code points, not counting line breaks, is one
+/// short of a multiple of four, the padding code point `☕` is appended before decoding. Returns
+/// the decoded data and whether the input was repaired this way; any other input is decoded
+/// exactly like with [`decode_str`](fn.decode_str.html).
+///
+/// The repair is a guess and can produce wrong data silently. It is only correct if the missing
+/// code point actually was a padding, i.e. the last group encoded three bytes. If it encoded four
+/// or five bytes instead, the repaired group decodes to three bytes, dropping the rest of the data
+/// without an error. Use it only when truncation by one code point is the known failure mode, and
+/// verify the result by other means if possible.
+///
+/// Failure conditions are the same as those of the [`decode`](fn.decode.html) function.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let mut encoded = ecoji::encode_to_string(&mut "input da".as_bytes())?;
+/// encoded.pop();
+/// assert_eq!(ecoji::decode_repair_one(&encoded)?, (b"input da".to_vec(), true));
+///
+/// // Here the missing code point was not a padding, so the repaired group only decodes " da"
+/// // and the last two bytes ("ta") of "input data" are lost
+/// assert_eq!(ecoji::decode_repair_one("👶😲🇲👅🍉🔙🌥")?, (b"input da".to_vec(), true));
+///
+/// assert_eq!(ecoji::decode_repair_one("👶😲🇲👅🍉🔙🌥🌩")?, (b"input data".to_vec(), false));
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn decode_repair_one(input: &str) -> io::Result<(Vec<u8>, bool)> {
+    let chars = input.chars().filter(|&c| c != '\n' && c != '\r').count();
+    if chars % CHARS_PER_GROUP != CHARS_PER_GROUP - 1 {
+        return decode_str(input).map(|output| (output, false));
+    }
+
+    let mut repaired = String::with_capacity(input.len() + MAX_EMOJI_UTF8_LEN);
+    repaired.push_str(input);
+    repaired.push(Alphabet::STANDARD.padding());
+    decode_str(&repaired).map(|output| (output, true))
+}
+
+/// Computes the number of bytes the given Ecoji-encoded string decodes to.
+///
+/// The length is computed from the padding of each group of the input, without decoding or
+/// validating anything else. Therefore, the result is exact only for valid input, including
+/// concatenated encodings; a trailing incomplete group is ignored. Line breaks are ignored, just
+/// like when decoding.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(ecoji::decoded_len("👶😲🇲👅🍉🔙🌥🌩"), 10);
+/// assert_eq!(ecoji::decoded_len("👶😲🇲👅\n🍉🔙🌥🌩\n"), 10);
+/// assert_eq!(ecoji::decoded_len("👖📸🎈☕"), 3);
+/// assert_eq!(ecoji::decoded_len(""), 0);
+/// ```
+pub fn decoded_len(input: &str) -> usize {
+    let mut chars = input.chars().filter(|&c| c != '\n' && c != '\r');
+
+    let mut len = 0;
+    let mut group = ['\0'; CHARS_PER_GROUP];
+    loop {
+        for slot in group.iter_mut() {
+            match chars.next() {
+                Some(c) => *slot = c,
+                None => return len,
+            }
+        }
+        len += group_len(&Alphabet::STANDARD, &group);
+    }
+}
+
+/// Checks whether the given Ecoji-encoded string ends with a complete group without padding.
+///
+/// Encoded strings which are group-aligned can be concatenated with other encoded strings, and the
+/// result is the same as the encoding of the concatenated data. Only the last four code points are
+/// inspected, so this does not validate the rest of the string. Line breaks are ignored, and an
+/// empty string is considered aligned.
+///
+/// # Examples
+///
+/// ```
+/// assert!(ecoji::is_group_aligned("👶😲🇲👅🍉🔙🌥🌩"));
+/// assert!(ecoji::is_group_aligned(""));
+/// assert!(!ecoji::is_group_aligned("👖📸🎈☕"));
+/// assert!(!ecoji::is_group_aligned("👶😲🇲"));
+/// ```
+pub fn is_group_aligned(encoded: &str) -> bool {
+    let mut last = ['\0'; CHARS_PER_GROUP];
+    let mut count = 0;
+    for (slot, c) in last.iter_mut().rev().zip(encoded.chars().rev().filter(|&c| c != '\n' && c != '\r')) {
+        *slot = c;
+        count += 1;
+    }
+
+    match count {
+        0 => true,
+        CHARS_PER_GROUP => group_len(&Alphabet::STANDARD, &last) == BYTES_PER_GROUP && !last.contains(&Alphabet::STANDARD.padding()),
+        _ => false,
+    }
+}
+
+/// Checks whether the given Ecoji-encoded string is canonical, i.e. whether encoding the data
+/// it decodes to gives exactly the same string.
+///
+/// Valid encoded data may still not be canonical, e.g. if unused bits of a padded group are not
+/// zero, if it contains line breaks, or if it is a concatenation of encodings with padding in the
+/// middle. Such data decodes fine, but a storage system may want to reject it to have a single
+/// representation of any data.
+///
+/// Returns an error if the string cannot be decoded at all, with the same failure conditions as
+/// those of the [`decode`](fn.decode.html) function.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// assert!(ecoji::is_canonical("👖📸🎈☕")?);
+///
+/// // Decodes to the same data, but has a non-zero unused bit in the third code point
+/// assert_eq!(ecoji::decode_str("👖📸🎉☕")?, b"abc");
+/// assert!(!ecoji::is_canonical("👖📸🎉☕")?);
+///
+/// assert!(ecoji::is_canonical("👖📸🎈").is_err());
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn is_canonical(encoded: &str) -> io::Result<bool> {
+    let decoded = decode_str(encoded)?;
+    let reencoded = ::encode::encode_to_string(&mut decoded.as_slice())?;
+    Ok(reencoded == encoded)
+}
+
+/// Checks whether the given string is valid Ecoji-encoded data, reporting the exact location of
+/// the first invalid code point.
+///
+/// Validation follows the same rules as decoding, so line breaks are ignored, but it does not
+/// produce any output. If a code point is not a part of the alphabet, returns an error of the
+/// `InvalidData` kind wrapping [`DecodeError::InvalidGroup`](enum.DecodeError.html), which
+/// describes the group containing it and its byte offset in the string, e.g. to highlight the
+/// problem in an editor. If a padding is in a slot of a group which must hold data, returns
+/// an error wrapping [`DecodeError::MisplacedPadding`](enum.DecodeError.html), like `decode`
+/// does. If the number of code points is not a multiple of 4, returns an error of the
+/// `UnexpectedEof` kind, like [`decode`](fn.decode.html) does.
+///
+/// # Examples
+///
+/// ```
+/// use ecoji::DecodeError;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// ecoji::validate("👖📸🎈☕")?;
+/// assert!(ecoji::validate("☕☕☕☕").is_err());
+///
+/// let error = ecoji::validate("👖📸🎈☕👖📸x☕").unwrap_err();
+/// assert_eq!(
+///     error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+///     Some(&DecodeError::InvalidGroup { found: 'x', group_index: 1, char_in_group: 2, byte_offset: 23 })
+/// );
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn validate(input: &str) -> io::Result<()> {
+    let mut first = None;
+    scan_groups(input, |e| {
+        first = Some(e);
+        false
+    });
+
+    match first {
+        Some(DecodeError::IncompleteGroup { .. }) => Err(incomplete_group_error()),
+        Some(e) => Err(e.into_io()),
+        None => Ok(()),
+    }
+}
+
+/// Checks whether the given string is valid Ecoji-encoded data, collecting all problems instead
+/// of stopping at the first one.
+///
+/// This is meant for tooling which repairs corrupted documents. Like
+/// [`validate`](fn.validate.html), every code point which is not a part of the alphabet is
+/// reported as [`DecodeError::InvalidGroup`](enum.DecodeError.html); after an invalid code point,
+/// the rest of its group is skipped and validation resumes at the next group boundary, so each
+/// group is reported at most once. A group of valid code points with a padding in a slot which
+/// must hold data is reported as [`DecodeError::MisplacedPadding`](enum.DecodeError.html). If
+/// the input ends with an incomplete group, the last error is
+/// [`DecodeError::IncompleteGroup`](enum.DecodeError.html).
+///
+/// Returns an empty vector exactly when [`validate`](fn.validate.html) succeeds.
+///
+/// # Examples
+///
+/// ```
+/// use ecoji::DecodeError;
+///
+/// let errors = ecoji::validate_all("👖x🎈y👖📸🎈☕👖📸z");
+/// assert_eq!(errors, [
+///     DecodeError::InvalidGroup { found: 'x', group_index: 0, char_in_group: 1, byte_offset: 4 },
+///     DecodeError::InvalidGroup { found: 'z', group_index: 2, char_in_group: 2, byte_offset: 33 },
+///     DecodeError::IncompleteGroup { group_index: 2, byte_offset: 25 },
+/// ]);
+///
+/// assert!(ecoji::validate_all("👖📸🎈☕").is_empty());
+/// ```
+pub fn validate_all(input: &str) -> Vec<DecodeError> {
+    let mut errors = Vec::new();
+    scan_groups(input, |e| {
+        errors.push(e);
+        true
+    });
+    errors
+}
+
+/// Counts the occurrences of each code point of the alphabet in the given Ecoji-encoded string.
+///
+/// This shows how the encoded data is distributed over the alphabet; e.g. data consisting
+/// mostly of zero bytes uses only a few distinct emojis. Paddings are counted like any other
+/// code point, and line breaks are ignored. The number of distinct code points used is the
+/// length of the returned map.
+///
+/// Only the code points are validated, not how they form groups. Returns an error of the
+/// `InvalidData` kind wrapping [`DecodeError::NotInAlphabet`](enum.DecodeError.html) if the
+/// string contains a code point which is not a part of the alphabet.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let encoded = ecoji::encode_to_string(&mut &[0; 20][..])?;
+/// let histogram = ecoji::emoji_histogram(&encoded)?;
+///
+/// assert_eq!(histogram.len(), 1);
+/// assert_eq!(histogram[&'🀄'], 16);
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn emoji_histogram(encoded: &str) -> io::Result<HashMap<char, usize>> {
+    let alphabet = &Alphabet::STANDARD;
+    let mut histogram = HashMap::new();
+    for (position, c) in encoded.chars().enumerate().filter(|&(_, c)| c != '\n' && c != '\r') {
+        if !alphabet.contains(c) {
+            return Err(DecodeError::NotInAlphabet { c, position: position as u64 }.into_io());
+        }
+        *histogram.entry(c).or_insert(0) += 1;
+    }
+    Ok(histogram)
+}
+
+/// Checks the code points of the input group by group, ignoring line breaks, and passes every
+/// problem to `report`; scanning stops as soon as `report` returns `false`.
+fn scan_groups<F: FnMut(DecodeError) -> bool>(input: &str, mut report: F) {
+    let alphabet = &Alphabet::STANDARD;
+    let mut count = 0;
+    let mut group_start = 0;
+    let mut invalid_group = None;
+    let mut chars = ['\0'; CHARS_PER_GROUP];
+    let mut positions = [0; CHARS_PER_GROUP];
+    let indexed = input.char_indices().enumerate().filter(|&(_, (_, c))| c != '\n' && c != '\r');
+    for (position, (byte_offset, found)) in indexed {
+        let (group_index, char_in_group) = (count / CHARS_PER_GROUP, count % CHARS_PER_GROUP);
+        if char_in_group == 0 {
+            group_start = byte_offset;
+        }
+        count += 1;
+
+        if invalid_group == Some(group_index) {
+            continue;
+        }
+        if !alphabet.contains(found) {
+            invalid_group = Some(group_index);
+            if !report(DecodeError::InvalidGroup { found, group_index, char_in_group, byte_offset }) {
+                return;
+            }
+            continue;
+        }
+
+        chars[char_in_group] = found;
+        positions[char_in_group] = position as u64;
+        if char_in_group == CHARS_PER_GROUP - 1 {
+            if let Some(i) = misplaced_padding(alphabet, &chars) {
+                if !report(DecodeError::MisplacedPadding { c: chars[i], position: positions[i] }) {
+                    return;
+                }
+            }
+        }
+    }
+
+    if count % CHARS_PER_GROUP != 0 {
+        report(DecodeError::IncompleteGroup { group_index: count / CHARS_PER_GROUP, byte_offset: group_start });
+    }
+}
+
+/// Decodes Ecoji data (assumed to be UTF-8-encoded) from the beginning of the input, stopping
+/// at the first group which cannot be decoded, and returns the decoded bytes together with the
+/// number of input bytes which were consumed.
+///
+/// This is meant for data followed by trailing garbage, e.g. data stored in zero-padded
+/// fixed-size records. Decoding also stops after a group with padding, since such a group can
+/// only be the last one. The consumed length points right after the last decoded group, so the
+/// input up to it is valid Ecoji data.
+///
+/// Returns an error if the input is not empty and its first group cannot be decoded, with the same
+/// failure conditions as those of the [`decode`](fn.decode.html) function.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let mut record = [0u8; 64];
+/// let encoded = "👶😲🇲👅🍉🔙🌥🌩".as_bytes();
+/// record[..encoded.len()].copy_from_slice(encoded);
+///
+/// let (output, consumed) = ecoji::decode_until_invalid(&record)?;
+/// assert_eq!(output, b"input data");
+/// assert_eq!(consumed, encoded.len());
+///
+/// assert!(ecoji::decode_until_invalid(&[0; 64]).is_err());
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn decode_until_invalid(input: &[u8]) -> io::Result<(Vec<u8>, usize)> {
+    let options = DecodeOptions::default();
+    let mut chars = Chars::new(input);
+    let mut position = 0;
+    let mut output = Vec::new();
+    let mut consumed = 0;
+
+    while let Some(group) = decode_group(&options, &mut chars, &mut position) {
+        let group = match group {
+            Ok(group) => group,
+            Err(_) if consumed > 0 => break,
+            Err(e) => return Err(e),
+        };
+        output.extend_from_slice(&group);
+        consumed = input.len() - chars.get_ref().len();
+        if group.len() < BYTES_PER_GROUP {
+            break;
+        }
+    }
+
+    Ok((output, consumed))
+}
+
+/// Computes which bytes of the decoded data each group of the given Ecoji-encoded string decodes
+/// to.
+///
+/// Returns a pair for each group, consisting of the offset in bytes of the group in the encoded
+/// string and the range of the decoded data which the group produces. Since the groups are
+/// independent, a part of the decoded data can be obtained by decoding just the groups covering
+/// it, e.g. with [`decode_str`](fn.decode_str.html) on a slice of the encoded string. Line breaks
+/// are ignored, just like when decoding.
+///
+/// Code points are checked to belong to the alphabet and paddings to be only in the slots which
+/// may hold them, but the data is not decoded, so failure conditions are the same as those of the [`decode`](fn.decode.html) function, except for
+/// errors of the destination.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let encoded = "👶😲🇲👅🍉🔙🌥🌩👖📸🎈☕";
+/// let ranges = ecoji::group_byte_ranges(encoded)?;
+///
+/// assert_eq!(ranges, [(0, 0..5), (16, 5..10), (32, 10..13)]);
+///
+/// // Decode only the groups covering the bytes 6..10
+/// let (start, ref range) = ranges[1];
+/// let output = ecoji::decode_str(&encoded[start..])?;
+/// assert_eq!(&output[6 - range.start..10 - range.start], b"data");
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn group_byte_ranges(encoded: &str) -> io::Result<Vec<(usize, Range<usize>)>> {
+    let alphabet = &Alphabet::STANDARD;
+    let mut ranges = Vec::with_capacity(encoded.len() / (CHARS_PER_GROUP * MIN_EMOJI_UTF8_LEN));
+    let mut decoded_len = 0;
+
+    let mut chars = encoded.char_indices().enumerate().filter(|&(_, (_, c))| c != '\n' && c != '\r');
+    while let Some((position, (start, c))) = chars.next() {
+        let mut group = ['\0'; CHARS_PER_GROUP];
+        let mut positions = [0; CHARS_PER_GROUP];
+        group[0] = check_char(alphabet, Ok(c), position as u64)?;
+        positions[0] = position as u64;
+        for i in 1..CHARS_PER_GROUP {
+            match chars.next() {
+                Some((position, (_, c))) => {
+                    group[i] = check_char(alphabet, Ok(c), position as u64)?;
+                    positions[i] = position as u64;
+                }
+                None => return Err(incomplete_group_error()),
+            }
+        }
+        if let Some(i) = misplaced_padding(alphabet, &group) {
+            return Err(DecodeError::MisplacedPadding { c: group[i], position: positions[i] }.into_io());
+        }
+
+        let len = group_len(alphabet, &group);
+        ranges.push((start, decoded_len..decoded_len + len));
+        decoded_len += len;
+    }
+
+    Ok(ranges)
+}
+
+/// Computes which bytes of the input data are encoded by the code point at the given index of
+/// the encoded output.
+///
+/// This is the per-character counterpart of [`group_byte_ranges`](fn.group_byte_ranges.html),
+/// useful e.g. for highlighting the source bytes covered by a selected emoji. The index counts
+/// only code points of the encoding itself, i.e. line breaks are not counted. Each code point
+/// carries ten bits, which generally straddle two bytes, so the returned range includes every
+/// byte of which at least one bit is encoded by the code point; the ranges of adjacent code
+/// points overlap.
+///
+/// The range is computed from the position alone and does not account for the length of
+/// the data: for a padding code point in the last group, it may extend past the end of the data
+/// or even be entirely beyond it, so it should be clamped to the data length by the caller.
+///
+/// # Examples
+///
+/// ```
+/// // The second code point holds the low 6 bits of byte 1 and the high 4 bits of byte 2
+/// assert_eq!(ecoji::char_offset_to_input_bytes(1), 1..3);
+/// assert_eq!(ecoji::char_offset_to_input_bytes(4), 5..7);
+/// assert_eq!(ecoji::char_offset_to_input_bytes(7), 8..10);
+/// ```
+pub fn char_offset_to_input_bytes(char_idx: usize) -> Range<usize> {
+    let group_start = char_idx / CHARS_PER_GROUP * BYTES_PER_GROUP;
+    let first_bit = char_idx % CHARS_PER_GROUP * BITS_PER_CHAR;
+    let end_bit = first_bit + BITS_PER_CHAR;
+    group_start + first_bit / 8..group_start + end_bit.div_ceil(8)
+}
+
+/// Splits an Ecoji-encoded string into the given number of shards, each of which can be decoded
+/// independently.
+///
+/// Shards are split on group boundaries and contain as close to the same number of groups as
+/// possible, so decoding the shards in order and concatenating the results gives the same data as
+/// decoding the whole string. If there are fewer groups than shards, the last shards are empty.
+/// Line breaks are ignored when counting code points, just like when decoding, and stay in the
+/// shards.
+///
+/// Only the number of code points is checked, not their validity, so decoding a shard may still
+/// fail.
+///
+/// Returns an error of the `UnexpectedEof` kind if the number of code points is not a multiple of
+/// 4, since then the string cannot be split without breaking a group.
+///
+/// # Panics
+///
+/// Panics if `num_shards` is zero.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let shards = ecoji::shard("👶😲🇲👅🍉🔙🌥🌩👖📸🎈☕", 2)?;
+///
+/// assert_eq!(shards, ["👶😲🇲👅🍉🔙🌥🌩", "👖📸🎈☕"]);
+/// assert_eq!(ecoji::decode_str(shards[0])?, b"input data");
+/// assert_eq!(ecoji::decode_str(shards[1])?, b"abc");
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn shard(encoded: &str, num_shards: usize) -> io::Result<Vec<&str>> {
+    assert!(num_shards > 0, "Number of shards must be positive");
+
+    let mut group_starts = Vec::new();
+    let mut chars = 0;
+    for (i, c) in encoded.char_indices() {
+        if c == '\n' || c == '\r' {
+            continue;
+        }
+        if chars % CHARS_PER_GROUP == 0 {
+            group_starts.push(i);
+        }
+        chars += 1;
+    }
+    if chars % CHARS_PER_GROUP != 0 {
+        return Err(incomplete_group_error());
+    }
+
+    let groups = group_starts.len();
+    let mut shards = Vec::with_capacity(num_shards);
+    let (mut start, mut group) = (0, 0);
+    for i in 0..num_shards {
+        group += groups / num_shards + if i < groups % num_shards { 1 } else { 0 };
+        let end = if i + 1 == num_shards {
+            encoded.len()
+        } else {
+            group_starts.get(group).cloned().unwrap_or(encoded.len())
+        };
+        shards.push(&encoded[start..end]);
+        start = end;
+    }
+
+    Ok(shards)
+}
+
+/// Decodes the entire source from the Ecoji format (assumed to be UTF-8-encoded), storing the
+/// result of the decoding to a new owned string.
+///
+/// Returns a string with the decoded data if successful.
+///
+/// In addition to the [`decode`](fn.decode.html) failure conditions, this function also returns
+/// an error if the decoded data is not a valid UTF-8 string.
+///
+/// # Examples
+///
+/// Successful decoding:
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let input = "👶😲🇲👅🍉🔙🌥🌩";
+/// let output: String = ecoji::decode_to_string(&mut input.as_bytes())?;
+///
+/// assert_eq!(output, "input data");
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+///
+/// Invalid input data, decoded string is not a valid UTF-8 string:
+///
+/// ```
+/// use std::io;
+///
+/// let input = "🧑🦲🧕🙋";  // Encoded data: [0xfe, 0xfe, 0xff, 0xff]
+///
+/// match ecoji::decode_to_string(&mut input.as_bytes()) {
+///     Ok(_) => panic!("Unexpected success"),
+///     Err(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+/// }
+/// ```
+pub fn decode_to_string<R: Read + ?Sized>(source: &mut R) -> io::Result<String> {
+    let output = decode_to_vec(source)?;
+    String::from_utf8(output).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Decodes the entire source from the Ecoji format (assumed to be UTF-8-encoded) into a new owned
+/// string, validating the decoded data as UTF-8 while it is being decoded.
+///
+/// Unlike [`decode_to_string`](fn.decode_to_string.html), this function appends decoded text to
+/// the string directly, without collecting the decoded bytes into a byte vector and validating
+/// them in a separate pass afterwards. Invalid data is detected as soon as it is decoded, so the
+/// rest of the input is not read in this case.
+///
+/// Failure conditions are exactly the same as those of the
+/// [`decode_to_string`](fn.decode_to_string.html) function.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let input = ecoji::encode_to_string(&mut "héllo wörld ✓".as_bytes())?;
+/// let output: String = ecoji::decode_to_string_streaming(&mut input.as_bytes())?;
+///
+/// assert_eq!(output, "héllo wörld ✓");
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn decode_to_string_streaming<R: Read + ?Sized>(source: &mut R) -> io::Result<String> {
+    let mut output = String::new();
+    // Bytes of a code point split across groups; a group is at least one byte long, so at most
+    // three bytes of a code point can be left over from previous groups
+    let mut pending = [0; 3 + BYTES_PER_GROUP];
+    let mut pending_len = 0;
+    let mut offset = 0;
+
+    for group in decode_groups(source) {
+        let group = group?;
+        pending[pending_len..pending_len + group.len()].copy_from_slice(&group);
+        let bytes = &pending[..pending_len + group.len()];
+
+        let valid_len = match str::from_utf8(bytes) {
+            Ok(s) => s.len(),
+            Err(ref e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(invalid_utf8_error(offset + e.valid_up_to())),
+        };
+        // validated just above
+        output.push_str(unsafe { str::from_utf8_unchecked(&bytes[..valid_len]) });
+
+        offset += valid_len;
+        pending_len = bytes.len() - valid_len;
+        pending.copy_within(valid_len..valid_len + pending_len, 0);
+    }
+
+    if pending_len > 0 {
+        return Err(invalid_utf8_error(offset));
+    }
+
+    Ok(output)
+}
+
+/// Decodes the entire source from the Ecoji format (assumed to be UTF-8-encoded) and appends the
+/// result to the provided string, reusing its allocated capacity.
+///
+/// Only the newly appended part is validated as UTF-8. If the decoding or the validation fails,
+/// the string is left unchanged.
+///
+/// If successful, returns the number of bytes which were appended to the string. Failure
+/// conditions are exactly the same as those of the [`decode_to_string`](fn.decode_to_string.html)
+/// function.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let mut output = String::with_capacity(64);
+/// for part in &["héllo ", "wörld ✓"] {
+///     let encoded = ecoji::encode_to_string(&mut part.as_bytes())?;
+///     ecoji::decode_into_string(&mut encoded.as_bytes(), &mut output)?;
+/// }
+///
+/// assert_eq!(output, "héllo wörld ✓");
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn decode_into_string<R: Read + ?Sized>(source: &mut R, destination: &mut String) -> io::Result<usize> {
+    let start = destination.len();
+    let mut bytes = ::std::mem::take(destination).into_bytes();
+
+    let result = decode(source, &mut bytes).and_then(|n| match str::from_utf8(&bytes[start..]) {
+        Ok(_) => Ok(n),
+        Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+    });
+    if result.is_err() {
+        bytes.truncate(start);
+    }
+
+    // the original string ends on a char boundary and the appended part was validated above
+    *destination = unsafe { String::from_utf8_unchecked(bytes) };
+    result
+}
+
+fn invalid_utf8_error(offset: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Decoded data is not valid UTF-8: invalid or incomplete sequence at offset {}", offset)
+    )
+}
+
+fn check_char(alphabet: &Alphabet, c: Result<char, CharsError>, position: u64) -> io::Result<char> {
+    c.map_err(CharsError::into_io).and_then(|c| if alphabet.contains(c) {
+        Ok(c)
+    } else {
+        Err(DecodeError::NotInAlphabet { c, position }.into_io())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emojis::*;
+
+    fn check(input: &[u8], output: &[u8]) {
+        let buf = decode_to_vec(&mut &input[..]).unwrap();
+        assert_eq!(output, buf.as_slice());
+    }
+
+    fn check_chars(input: &[char], output: &[u8]) {
+        let input: String = input.iter().cloned().collect();
+        let buf = decode_to_vec(&mut input.as_bytes()).unwrap();
+        assert_eq!(output, buf.as_slice());
+    }
+
+    #[test]
+    fn test_random() {
+        check("👖📸🎈☕".as_bytes(), b"abc");
+    }
+
+    #[test]
+    fn test_one_byte() {
+        check_chars(&[EMOJIS[('k' as usize) << 2], PADDING, PADDING, PADDING], b"k");
+    }
+
+    #[test]
+    fn test_two_bytes() {
+        check_chars(&[EMOJIS[0], EMOJIS[16], PADDING, PADDING], &[0, 1]);
+    }
+
+    #[test]
+    fn test_three_bytes() {
+        check_chars(&[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING], &[0, 1, 2]);
+    }
+
+    #[test]
+    fn test_four_bytes() {
+        check_chars(&[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING_40], &[0, 1, 2, 0]);
+        check_chars(&[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING_41], &[0, 1, 2, 1]);
+        check_chars(&[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING_42], &[0, 1, 2, 2]);
+        check_chars(&[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING_43], &[0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_four_bytes_with_misplaced_padding() {
+        for &padding in &[PADDING, PADDING_40, PADDING_41, PADDING_42, PADDING_43] {
+            for slot in 0..3 {
+                let mut group = [EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING_41];
+                group[slot] = padding;
+                let input: String = [EMOJIS[1]; 4].iter().chain(&group).collect();
+
+                let error = decode_str(&input).unwrap_err();
+                assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+                assert_eq!(
+                    error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+                    Some(&DecodeError::MisplacedPadding { c: padding, position: 4 + slot as u64 })
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_padding_in_data_slot() {
+        for &padding in &[PADDING, PADDING_40, PADDING_41, PADDING_42, PADDING_43] {
+            for slot in 0..3 {
+                let mut group = [EMOJIS[0], EMOJIS[16], EMOJIS[128], EMOJIS[512]];
+                group[slot] = padding;
+                let input: String = [EMOJIS[1]; 4].iter().chain(&group).collect();
+
+                let expected = DecodeError::MisplacedPadding { c: padding, position: 4 + slot as u64 };
+                let error = decode_str(&input).unwrap_err();
+                assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+                assert_eq!(error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()), Some(&expected));
+
+                let error = validate(&input).unwrap_err();
+                assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+                assert_eq!(error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()), Some(&expected));
+                assert_eq!(validate_all(&input), [expected]);
+            }
+        }
+
+        // Groups consisting of paddings only, or with a 4-byte group padding after a padding
+        let invalid_groups = [
+            ([PADDING, PADDING, PADDING, PADDING], 0),
+            ([PADDING_42, PADDING, PADDING, PADDING], 0),
+            ([EMOJIS[0], PADDING, PADDING, PADDING_40], 1),
+            ([EMOJIS[0], PADDING_43, PADDING, PADDING], 1),
+            ([EMOJIS[0], EMOJIS[16], PADDING, PADDING_41], 2),
+        ];
+        for &(group, slot) in &invalid_groups {
+            let input: String = group.iter().collect();
+            let expected = DecodeError::MisplacedPadding { c: group[slot], position: slot as u64 };
+            let error = decode_str(&input).unwrap_err();
+            assert_eq!(error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()), Some(&expected));
+
+            // Validation follows the same rules
+            let error = validate(&input).unwrap_err();
+            assert_eq!(error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()), Some(&expected));
+            assert_eq!(validate_all(&input), [expected]);
+        }
+
+        // Positions count line breaks, like when decoding
+        let input: String = ['\n', EMOJIS[3], '\n', PADDING, EMOJIS[3], PADDING].iter().collect();
+        let expected = DecodeError::MisplacedPadding { c: PADDING, position: 3 };
+        assert_eq!(decode_str(&input).unwrap_err().get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+                   Some(&expected));
+        assert_eq!(validate_all(&input), [expected]);
+        assert_eq!(validate_all("👖☕📸☕"), [DecodeError::MisplacedPadding { c: PADDING, position: 1 }]);
+    }
+
+    #[test]
+    fn test_five_bytes() {
+        check_chars(&[EMOJIS[687], EMOJIS[222], EMOJIS[960], EMOJIS[291]], &[0xAB, 0xCD, 0xEF, 0x01, 0x23]);
+    }
+
+    #[test]
+    fn test_decoded_len() {
+        let chars = |cs: &[char]| cs.iter().cloned().collect::<String>();
+
+        assert_eq!(decoded_len(&chars(&[EMOJIS[0], PADDING, PADDING, PADDING])), 1);
+        assert_eq!(decoded_len(&chars(&[EMOJIS[0], EMOJIS[16], PADDING, PADDING])), 2);
+        assert_eq!(decoded_len(&chars(&[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING])), 3);
+        assert_eq!(decoded_len(&chars(&[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING_42])), 4);
+        assert_eq!(decoded_len(&chars(&[EMOJIS[0], EMOJIS[16], EMOJIS[128], EMOJIS[1]])), 5);
+        assert_eq!(decoded_len(&chars(&[EMOJIS[0], EMOJIS[16], EMOJIS[128], EMOJIS[1], EMOJIS[0], PADDING, PADDING, PADDING])), 6);
+        // a trailing incomplete group is ignored
+        assert_eq!(decoded_len(&chars(&[EMOJIS[0], EMOJIS[16], EMOJIS[128], EMOJIS[1], EMOJIS[0]])), 5);
+        // concatenated encodings
+        assert_eq!(decoded_len("👖📸🎈☕👖📸🎈☕"), 6);
+    }
+
+    #[test]
+    fn test_is_group_aligned() {
+        let data: Vec<u8> = (0..23).collect();
+        for len in 0..data.len() {
+            let encoded = ::encode_to_string(&mut &data[..len]).unwrap();
+            assert_eq!(is_group_aligned(&encoded), len % 5 == 0, "{}", len);
+            assert_eq!(is_group_aligned(&format!("{}\n", encoded)), len % 5 == 0);
+        }
+
+        assert!(!is_group_aligned(&[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING_40].iter().collect::<String>()));
+    }
+
+    #[test]
+    fn test_is_canonical() {
+        let data: Vec<u8> = (0..23).collect();
+        for len in 0..data.len() {
+            let encoded = ::encode_to_string(&mut &data[..len]).unwrap();
+            assert!(is_canonical(&encoded).unwrap());
+            if len > 0 {
+                assert!(!is_canonical(&format!("{}\n", encoded)).unwrap());
+            }
+        }
+
+        assert!(!is_canonical("👖📸🎈☕👖📸🎈☕").unwrap());
+        assert!(is_canonical("👶😲🇲👅👖📸🎈☕").unwrap());
+        assert!(is_canonical("★").is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let data: Vec<u8> = (0..23).collect();
+        for len in 0..data.len() {
+            let encoded = ::encode_to_string(&mut &data[..len]).unwrap();
+            validate(&encoded).unwrap();
+            validate(&encoded.replace("", "\n")).unwrap();
+        }
+
+        let error = validate("👖📸🎈☕\r\n👖★🎈☕").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+            Some(&DecodeError::InvalidGroup { found: '★', group_index: 1, char_in_group: 1, byte_offset: 21 })
+        );
+        assert_eq!(error.to_string(), "invalid char '★' (U+2605) at index 1 of group 1 (byte offset 21)");
+
+        assert_eq!(validate("👖📸🎈").unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(validate("👖📸🎈x").unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_char_offset_to_input_bytes() {
+        let data: Vec<u8> = (0..10u32).map(|i| (i * 29 + 3) as u8).collect();
+        let encoded: Vec<char> = ::encode_to_string(&mut data.as_slice()).unwrap().chars().collect();
+
+        for (i, &c) in encoded.iter().enumerate() {
+            let range = char_offset_to_input_bytes(i);
+            assert!(range.len() == 2, "code point {} covers {:?}", i, range);
+
+            // Flipping a byte in the range changes the code point, flipping any other byte does not
+            for j in 0..data.len() {
+                let mut changed = data.clone();
+                changed[j] ^= 0xff;
+                let changed: Vec<char> = ::encode_to_string(&mut changed.as_slice()).unwrap().chars().collect();
+                assert_eq!(changed[i] != c, range.contains(&j), "code point {}, byte {}", i, j);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_repair_one() {
+        let data: Vec<u8> = (0..13u32).map(|i| (i * 41 + 7) as u8).collect();
+        for len in 0..data.len() {
+            let encoded = ::encode_to_string(&mut &data[..len]).unwrap();
+            assert_eq!(decode_repair_one(&encoded).unwrap(), (data[..len].to_vec(), false));
+
+            let mut truncated = encoded.clone();
+            let last = truncated.pop();
+            let expected_len = match len % BYTES_PER_GROUP {
+                0 if len > 0 => len - 2,
+                4 => len - 1,
+                _ => len,
+            };
+            if len > 0 {
+                let (output, repaired) = decode_repair_one(&truncated).unwrap();
+                assert!(repaired);
+                assert_eq!(output, &data[..expected_len]);
+                assert_eq!(output.len() == len, last == Some(Alphabet::STANDARD.padding()));
+            }
+        }
+
+        // Line breaks are not counted, and other truncations are not repaired
+        assert_eq!(decode_repair_one("👶😲🇲\n👅🍉🔙\n🌥").unwrap(), (b"input da".to_vec(), true));
+        assert_eq!(decode_repair_one("👶😲🇲👅🍉🔙").unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(decode_repair_one("👶😲★").unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_decode_with_progress() {
+        let data: Vec<u8> = (0..60u32).map(|i| (i * 13 + 1) as u8).collect();
+        let encoded = ::encode_to_string(&mut data.as_slice()).unwrap();
+
+        let cases = [(1, (1..=12).collect()), (5, vec![5, 10, 12]), (12, vec![12]), (100, vec![12])];
+        for &(every_n_groups, ref expected) in &cases {
+            let mut reports: Vec<u64> = Vec::new();
+            let mut output = Vec::new();
+            let n = decode_with_progress(&mut encoded.as_bytes(), &mut output, every_n_groups,
+                                         |groups| reports.push(groups)).unwrap();
+            assert_eq!(n, data.len());
+            assert_eq!(output, data);
+            assert_eq!(reports, *expected);
+        }
+
+        let mut reports = Vec::new();
+        decode_with_progress(&mut "".as_bytes(), &mut Vec::new(), 1, |groups| reports.push(groups)).unwrap();
+        assert!(reports.is_empty());
+
+        let mut reports = Vec::new();
+        let error = decode_with_progress(&mut "👶😲🇲👅🍉🔙★🌩".as_bytes(), &mut Vec::new(), 1, |groups| reports.push(groups))
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(reports, [1]);
+    }
+
+    #[test]
+    fn test_decode_cursor() {
+        let mut source = Cursor::new("prefix 👶😲🇲👅🍉🔙🌥🌩");
+        source.set_position(7);
+        let mut output = decode_cursor(&mut source).unwrap();
+        assert_eq!(output.get_ref().as_slice(), b"input data");
+        assert_eq!(output.position(), 10);
+        assert_eq!(source.position(), source.get_ref().len() as u64);
+
+        output.write_all(b"!").unwrap();
+        assert_eq!(output.into_inner(), b"input data!");
+
+        assert_eq!(decode_cursor(&mut Cursor::new("👶😲★")).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_decode_exact() {
+        let data: Vec<u8> = (0..23).map(|i| i * 7).collect();
+        for len in 0..data.len() {
+            let encoded = ::encode_to_string(&mut &data[..len]).unwrap();
+            let mut output = vec![0; len];
+            decode_exact(&encoded, &mut output).unwrap();
+            assert_eq!(output, &data[..len]);
+
+            for &out_len in &[len.saturating_sub(1), len + 1, len + 5] {
+                if out_len == len {
+                    continue;
+                }
+                let error = decode_exact(&encoded, &mut vec![0; out_len]).unwrap_err();
+                assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+                assert_eq!(
+                    error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+                    Some(&DecodeError::LengthMismatch { expected: out_len, actual: len })
+                );
+            }
+        }
+
+        assert_eq!(decode_exact("👖📸🎈", &mut [0; 3]).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_emoji_histogram() {
+        let histogram = emoji_histogram("👖📸🎈☕\n👖📸🎈☕👕☕☕☕").unwrap();
+        assert_eq!(histogram.len(), 5);
+        assert_eq!(histogram[&PADDING], 5);
+        assert_eq!(histogram[&'👖'], 2);
+        assert_eq!(histogram[&'👕'], 1);
+        assert_eq!(histogram.values().sum::<usize>(), 12);
+
+        assert!(emoji_histogram("").unwrap().is_empty());
+
+        let error = emoji_histogram("👖\n★").unwrap_err();
+        assert_eq!(
+            error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+            Some(&DecodeError::NotInAlphabet { c: '★', position: 2 })
+        );
+    }
+
+    #[test]
+    fn test_validate_all() {
+        let inputs = ["", "👖📸🎈☕", "👖📸🎈", "★", "👖★★☕\n★📸🎈☕", "👖📸🎈☕\r\n👖★🎈☕👖"];
+        for input in inputs.iter() {
+            assert_eq!(validate_all(input).is_empty(), validate(input).is_ok());
+        }
+
+        assert_eq!(validate_all("👖★★☕\n★📸🎈☕👖"), [
+            DecodeError::InvalidGroup { found: '★', group_index: 0, char_in_group: 1, byte_offset: 4 },
+            DecodeError::InvalidGroup { found: '★', group_index: 1, char_in_group: 0, byte_offset: 14 },
+            DecodeError::IncompleteGroup { group_index: 2, byte_offset: 28 },
+        ]);
+        assert_eq!(
+            DecodeError::IncompleteGroup { group_index: 2, byte_offset: 28 }.to_string(),
+            "incomplete group 2 at byte offset 28, input code points count is not a multiple of 4"
+        );
+    }
+
+    #[test]
+    fn test_decode_str() {
+        assert_eq!(decode_str("👖📸🎈☕").unwrap(), b"abc");
+
+        let empty = decode_str("").unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(empty.capacity(), 0);
+    }
+
+    #[test]
+    fn test_limited() {
+        let input = "👖📸🎈☕";
+        assert_eq!(decode_to_vec_limited(&mut input.as_bytes(), 3).unwrap(), b"abc");
+        assert_eq!(decode_to_vec_limited(&mut "".as_bytes(), 0).unwrap(), b"");
+
+        let error = decode_to_vec_limited(&mut input.as_bytes(), 2).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+            Some(&DecodeError::OutputTooLarge { limit: 2 })
+        );
+    }
+
+    #[test]
+    fn test_decoder() {
+        let data: Vec<u8> = (0..64).collect();
+        for len in 0..data.len() {
+            let encoded = ::encode_to_string(&mut &data[..len]).unwrap();
+            for buf_size in 1..8 {
+                let mut decoder = Decoder::new(encoded.as_bytes());
+                let mut output = Vec::new();
+                let mut buf = vec![0; buf_size];
+                loop {
+                    match decoder.read(&mut buf).unwrap() {
+                        0 => break,
+                        n => output.extend_from_slice(&buf[..n]),
+                    }
+                }
+                assert_eq!(output, &data[..len]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decoded_bytes() {
+        let data: Vec<u8> = (0..64).map(|i| i * 3).collect();
+        for len in 0..data.len() {
+            let encoded = ::encode_to_string(&mut &data[..len]).unwrap();
+            let output = DecodedBytes::new(&encoded).collect::<io::Result<Vec<u8>>>().unwrap();
+            assert_eq!(output, &data[..len]);
+        }
+
+        let mut bytes = DecodedBytes::new("👖📸🎈☕👖★🎈☕");
+        assert_eq!(bytes.by_ref().take(3).map(Result::unwrap).collect::<Vec<_>>(), b"abc");
+        let error = bytes.next().unwrap().unwrap_err();
+        assert_eq!(
+            error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+            Some(&DecodeError::NotInAlphabet { c: '★', position: 5 })
+        );
+        assert!(bytes.next().is_none());
+
+        let mut bytes = DecodedBytes::new("👖📸🎈");
+        assert_eq!(bytes.next().unwrap().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert!(bytes.next().is_none());
+    }
+
+    #[test]
+    fn test_decoder_debug_and_clone() {
+        let mut decoder = Decoder::new("👖📸🎈☕".as_bytes());
+        let mut buf = [0; 1];
+        decoder.read_exact(&mut buf).unwrap();
+
+        let debug = format!("{:?}", decoder);
+        assert_eq!(debug, r#"Decoder { inner: [], alphabet: Alphabet("standard"), buffered: 2 }"#);
+
+        let mut clone = decoder.clone();
+        let (mut rest, mut clone_rest) = (Vec::new(), Vec::new());
+        decoder.read_to_end(&mut rest).unwrap();
+        clone.read_to_end(&mut clone_rest).unwrap();
+        assert_eq!(rest, b"bc");
+        assert_eq!(clone_rest, b"bc");
+    }
+
+    #[test]
+    fn test_decoder_buf_read() {
+        let text = "line one\n\nline three is a bit longer\nno newline at the end";
+        let encoded = ::encode_to_string(&mut text.as_bytes()).unwrap();
+
+        let lines: Vec<_> = Decoder::new(encoded.as_bytes()).lines().map(Result::unwrap).collect();
+        assert_eq!(lines, text.lines().collect::<Vec<_>>());
+
+        let mut decoder = Decoder::new(encoded.as_bytes());
+        assert_eq!(decoder.fill_buf().unwrap(), b"line ");
+        decoder.consume(2);
+        assert_eq!(decoder.fill_buf().unwrap(), b"ne ");
+        decoder.consume(10);
+        assert_eq!(decoder.fill_buf().unwrap(), b"one\n\n");
+
+        let mut decoder = Decoder::new("👖📸🎈".as_bytes());
+        assert!(decoder.fill_buf().is_err());
+    }
+
+    #[test]
+    fn test_decoder_reset() {
+        let messages: Vec<_> = ["first message", "", "x", "the last message"].iter()
+            .map(|m| ::encode_to_string(&mut m.as_bytes()).unwrap())
+            .collect();
+        let stream = messages.concat();
+
+        let mut decoder = Decoder::new(stream.as_bytes().take(0));
+        for (message, encoded) in ["first message", "", "x", "the last message"].iter().zip(&messages) {
+            decoder.get_mut().set_limit(encoded.len() as u64);
+            decoder.reset();
+            let mut output = String::new();
+            decoder.read_to_string(&mut output).unwrap();
+            assert_eq!(output, *message);
+        }
+
+        // without a reset the decoder does not read past the end of a message
+        let mut decoder = Decoder::new(stream.as_bytes().take(messages[0].len() as u64));
+        decoder.read_to_end(&mut Vec::new()).unwrap();
+        decoder.get_mut().set_limit(u64::MAX);
+        assert_eq!(decoder.read(&mut [0; 8]).unwrap(), 0);
+
+        // offsets in errors are relative to the start of the current message
+        let mut decoder = Decoder::new(stream.as_bytes().take(messages[0].len() as u64));
+        decoder.read_to_end(&mut Vec::new()).unwrap();
+        *decoder.get_mut() = b"\xff".take(1);
+        decoder.reset();
+        let error = decoder.read(&mut [0; 8]).unwrap_err();
+        assert!(error.to_string().ends_with("at offset 0"), "{}", error);
+    }
+
+    #[test]
+    fn test_not_in_alphabet_position() {
+        let not_in_alphabet = |input: &str, options: &DecodeOptions| {
+            let error = options.decode_to_vec(&mut input.as_bytes()).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            let result = error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()).cloned();
+            (result, error.to_string())
+        };
+        let options = DecodeOptions::new();
+
+        assert_eq!(
+            not_in_alphabet("👶😲🇲👅🍉🔙★🌩", &options),
+            (Some(DecodeError::NotInAlphabet { c: '★', position: 6 }), "invalid char '★' (U+2605) at position 6".into())
+        );
+        assert_eq!(
+            not_in_alphabet("👶😲🇲👅\r\n🍉🔙\t🌩", &options),
+            (Some(DecodeError::NotInAlphabet { c: '\t', position: 8 }), "invalid char '\\t' (U+0009) at position 8".into())
+        );
+
+        let options = DecodeOptions::new().ignore_chars(&['\u{200D}']);
+        assert_eq!(
+            not_in_alphabet("👶\u{200D}😲\u{200D}🇲\u{200D}👅x", &options).0,
+            Some(DecodeError::NotInAlphabet { c: 'x', position: 7 })
+        );
+
+        let mut groups = decode_groups("👶😲🇲👅🍉🔙🌥a".as_bytes());
+        assert!(groups.next().unwrap().is_ok());
+        let error = groups.next().unwrap().unwrap_err();
+        assert_eq!(error.to_string(), "invalid char 'a' (U+0061) at position 7");
+    }
+
+    #[test]
+    fn test_with_hasher() {
+        struct CollectingHasher(Vec<u8>);
+
+        impl Hasher for CollectingHasher {
+            fn write(&mut self, bytes: &[u8]) { self.0.extend_from_slice(bytes) }
+            fn finish(&self) -> u64 { self.0.len() as u64 }
+        }
+
+        /// Accepts at most two bytes per write.
+        struct ShortWriter(Vec<u8>);
+
+        impl Write for ShortWriter {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                let n = cmp::min(buf.len(), 2);
+                self.0.extend_from_slice(&buf[..n]);
+                Ok(n)
+            }
+            fn flush(&mut self) -> io::Result<()> { Ok(()) }
+        }
+
+        let data: Vec<u8> = (0..64).collect();
+        let encoded = ::encode_to_string(&mut data.as_slice()).unwrap();
+
+        let (mut output, mut hasher) = (ShortWriter(Vec::new()), CollectingHasher(Vec::new()));
+        assert_eq!(decode_with_hasher(&mut encoded.as_bytes(), &mut output, &mut hasher).unwrap(), 64);
+        assert_eq!(output.0, data);
+        assert_eq!(hasher.0, data);
+
+        let mut hasher = CollectingHasher(Vec::new());
+        let truncated = &encoded.as_bytes()[..encoded.len() - 4];
+        assert!(decode_with_hasher(&mut &truncated[..], &mut Vec::new(), &mut hasher).is_err());
+        assert_eq!(hasher.0, &data[..60]);
+    }
+
+    #[test]
+    fn test_to_string_streaming() {
+        let text = "ascii, ünïcödé, ✓✗, 🦀🎉 and more ascii";
+        for start in 0..text.len() {
+            if !text.is_char_boundary(start) {
+                continue;
+            }
+            let encoded = ::encode_to_string(&mut &text.as_bytes()[start..]).unwrap();
+            assert_eq!(decode_to_string_streaming(&mut encoded.as_bytes()).unwrap(), &text[start..]);
+        }
+
+        let invalid: &[(&[u8], &str)] = &[
+            (b"\xff", "at offset 0"),
+            (b"abcdef\xc3", "at offset 6"),
+            (b"abcd\xf0\x9f\xa6", "at offset 4"),
+            (b"abcd\xf0\x9f\xa6x", "at offset 4"),
+            (b"\xe2\x9c\x93\xe2\x9c", "at offset 3"),
+        ];
+        for &(data, message) in invalid {
+            let encoded = ::encode_to_string(&mut &data[..]).unwrap();
+            let error = decode_to_string_streaming(&mut encoded.as_bytes()).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert!(error.to_string().ends_with(message), "{}", error);
+            assert!(decode_to_string(&mut encoded.as_bytes()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_codepoints() {
+        let data: Vec<u8> = (0..23).collect();
+        let encoded = ::encode_to_string(&mut data.as_slice()).unwrap();
+        let points: Vec<u32> = encoded.chars().map(|c| c as u32).collect();
+
+        let mut output = Vec::new();
+        assert_eq!(decode_codepoints(&points, &mut output).unwrap(), data.len());
+        assert_eq!(output, data);
+
+        let mut invalid = points.clone();
+        invalid[5] = 0x110000;
+        let error = decode_codepoints(&invalid, &mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "code point 0x110000 at offset 5 is not a valid unicode scalar value");
+
+        // valid scalar values which are not in the alphabet
+        let error = decode_codepoints(&[0x41, 0x42, 0x43, 0x44], &mut Vec::new()).unwrap_err();
+        assert_eq!(
+            error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+            Some(&DecodeError::NotInAlphabet { c: 'A', position: 0 })
+        );
+    }
+
+    #[test]
+    fn test_from_indices() {
+        let data: Vec<u8> = (0..24).map(|i| i * 11).collect();
+        for len in 0..data.len() {
+            assert_eq!(decode_from_indices(&::encode_to_indices(&data[..len])).unwrap(), &data[..len]);
+        }
+
+        assert_eq!(decode_from_indices(&[421, 743]).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        let error = decode_from_indices(&[421, 743, 29, 1029]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "invalid code point index 1029 at position 3");
+    }
+
+    #[test]
+    fn test_take() {
+        let data: Vec<u8> = (0..23).collect();
+        let encoded = ::encode_to_string(&mut data.as_slice()).unwrap();
+
+        for limit in 0..30 {
+            let mut input = encoded.as_bytes();
+            let mut output = Vec::new();
+            let (n, reached) = decode_take(&mut input, &mut output, limit).unwrap();
+
+            let expected = cmp::min(limit, data.len());
+            assert_eq!(n, expected);
+            assert_eq!(reached, limit <= data.len());
+            assert_eq!(output, &data[..expected]);
+
+            // only the groups which were needed have been read
+            let groups_read = expected.div_ceil(5);
+            assert_eq!(input, encoded.chars().skip(groups_read * 4).collect::<String>().as_bytes());
+        }
+
+        // errors after the limit are not reported
+        let input = "👶😲🇲👅★";
+        assert_eq!(decode_take(&mut input.as_bytes(), &mut Vec::new(), 5).unwrap(), (5, true));
+        assert!(decode_take(&mut input.as_bytes(), &mut Vec::new(), 6).is_err());
+    }
+
+    #[test]
+    fn test_into_string() {
+        let text = "ascii, ünïcödé, ✓✗, 🦀🎉";
+        for split in (0..=text.len()).filter(|&i| text.is_char_boundary(i)) {
+            let mut output = String::from(&text[..split]);
+            let encoded = ::encode_to_string(&mut &text.as_bytes()[split..]).unwrap();
+            let n = decode_into_string(&mut encoded.as_bytes(), &mut output).unwrap();
+            assert_eq!(n, text.len() - split);
+            assert_eq!(output, text);
+        }
+
+        // a code point split in the new region
+        let mut output = String::from("✓");
+        let encoded = ::encode_to_string(&mut &b"ab\xf0\x9f\xa6"[..]).unwrap();
+        assert!(decode_into_string(&mut encoded.as_bytes(), &mut output).is_err());
+        assert_eq!(output, "✓");
+
+        // invalid Ecoji data
+        let mut output = String::with_capacity(32);
+        output.push_str("prefix");
+        let capacity = output.capacity();
+        assert!(decode_into_string(&mut "👶😲🇲".as_bytes(), &mut output).is_err());
+        assert_eq!(output, "prefix");
+        assert_eq!(output.capacity(), capacity);
+    }
+
+    /// Fails every other call with `Interrupted` and accepts at most three bytes otherwise.
+    struct InterruptingWriter {
+        data: Vec<u8>,
+        calls: usize,
+    }
+
+    impl Write for InterruptingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.calls += 1;
+            if self.calls % 2 == 1 {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+            }
+            let n = cmp::min(buf.len(), 3);
+            self.data.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    /// Fails every other call with `Interrupted` and reads at most two bytes otherwise.
+    struct InterruptingReader<'a> {
+        data: &'a [u8],
+        calls: usize,
+    }
+
+    impl<'a> Read for InterruptingReader<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.calls += 1;
+            if self.calls % 2 == 1 {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+            }
+            let n = cmp::min(buf.len(), 2);
+            (&mut self.data).take(n as u64).read(buf)
+        }
+    }
+
+    #[test]
+    fn test_interrupted_io() {
+        struct CollectingHasher(Vec<u8>);
+
+        impl Hasher for CollectingHasher {
+            fn write(&mut self, bytes: &[u8]) { self.0.extend_from_slice(bytes) }
+            fn finish(&self) -> u64 { 0 }
+        }
+
+        let data: Vec<u8> = (0..64).collect();
+        for len in 0..data.len() {
+            let encoded = ::encode_to_string(&mut &data[..len]).unwrap();
+
+            let mut output = InterruptingWriter { data: Vec::new(), calls: 0 };
+            assert_eq!(decode(&mut encoded.as_bytes(), &mut output).unwrap(), len);
+            assert_eq!(output.data, &data[..len]);
+
+            let mut output = InterruptingWriter { data: Vec::new(), calls: 0 };
+            let mut hasher = CollectingHasher(Vec::new());
+            decode_with_hasher(&mut encoded.as_bytes(), &mut output, &mut hasher).unwrap();
+            assert_eq!(output.data, &data[..len]);
+            assert_eq!(hasher.0, &data[..len]);
+
+            let mut input = InterruptingReader { data: encoded.as_bytes(), calls: 0 };
+            let mut output = InterruptingWriter { data: Vec::new(), calls: 0 };
+            decode(&mut input, &mut output).unwrap();
+            assert_eq!(output.data, &data[..len]);
+
+            let mut input = InterruptingReader { data: encoded.as_bytes(), calls: 0 };
+            let mut output = Vec::new();
+            Decoder::new(&mut input).read_to_end(&mut output).unwrap();
+            assert_eq!(output, &data[..len]);
+        }
+    }
+
+    #[test]
+    fn test_until_invalid() {
+        let data: Vec<u8> = (0..23).collect();
+        for len in 1..data.len() {
+            let encoded = ::encode_to_string(&mut &data[..len]).unwrap();
+            for garbage in &[&b"\0\0\0\0\0\0\0\0"[..], b"", b"\xff\xfe", "👶😲".as_bytes()] {
+                let input = [encoded.as_bytes(), garbage].concat();
+                let (output, consumed) = decode_until_invalid(&input).unwrap();
+                assert_eq!(output, &data[..len]);
+                assert_eq!(consumed, encoded.len());
+            }
+        }
+
+        assert_eq!(decode_until_invalid(b"").unwrap(), (Vec::new(), 0));
+        assert_eq!(decode_until_invalid(b"\0").unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(decode_until_invalid("👶😲🇲".as_bytes()).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+        // aligned data followed by more valid groups is decoded further
+        let input = "👶😲🇲👅🍉🔙🌥🌩👖📸🎈☕\0\0";
+        assert_eq!(decode_until_invalid(input.as_bytes()).unwrap(), (b"input dataabc".to_vec(), input.len() - 2));
+    }
+
+    #[test]
+    fn test_group_byte_ranges() {
+        let data: Vec<u8> = (0..23).collect();
+        let encoded = ::encode_to_string(&mut data.as_slice()).unwrap();
+        let wrapped: String = encoded.chars().enumerate()
+            .flat_map(|(i, c)| if i % 6 == 5 { vec![c, '\n'] } else { vec![c] })
+            .collect();
+
+        let ranges = group_byte_ranges(&wrapped).unwrap();
+        assert_eq!(ranges.len(), 5);
+        assert_eq!(ranges.last().unwrap().1, 20..23);
+        for (start, range) in ranges {
+            let group: String = wrapped[start..].chars().filter(|&c| c != '\n').take(4).collect();
+            assert_eq!(decode_str(&group).unwrap(), &data[range]);
+        }
+
+        // concatenated encodings
+        let ranges = group_byte_ranges("👖📸🎈☕👶😲🇲👅").unwrap();
+        assert_eq!(ranges, [(0, 0..3), (15, 3..8)]);
+
+        assert_eq!(group_byte_ranges("").unwrap(), []);
+        assert_eq!(group_byte_ranges("👶😲🇲").unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        let error = group_byte_ranges("👶😲\n★👅").unwrap_err();
+        assert_eq!(
+            error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+            Some(&DecodeError::NotInAlphabet { c: '★', position: 3 })
+        );
+
+        // paddings in data slots are rejected, like when decoding
+        for &(input, position) in &[("☕☕☕☕", 0), ("👶😲🇲👅\n☕😲🇲👅", 5)] {
+            let expected = DecodeError::MisplacedPadding { c: '☕', position };
+            let error = group_byte_ranges(input).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()), Some(&expected));
+            let error = decode_str(input).unwrap_err();
+            assert_eq!(error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()), Some(&expected));
+        }
+    }
+
+    #[test]
+    fn test_shard() {
+        let data: Vec<u8> = (0..103u32).map(|i| (i * 7) as u8).collect();
+        let encoded = ::encode_to_string(&mut data.as_slice()).unwrap();
+
+        for num_shards in 1..30 {
+            let shards = shard(&encoded, num_shards).unwrap();
+            assert_eq!(shards.len(), num_shards);
+            assert_eq!(shards.concat(), encoded);
+
+            let mut decoded = Vec::new();
+            for s in &shards {
+                assert_eq!(s.chars().count() % 4, 0);
+                decoded.extend(decode_str(s).unwrap());
+            }
+            assert_eq!(decoded, data);
+        }
+
+        let wrapped = "👶😲🇲👅\n🍉🔙🌥🌩\n👖📸🎈☕\n";
+        assert_eq!(shard(wrapped, 2).unwrap(), ["👶😲🇲👅\n🍉🔙🌥🌩\n", "👖📸🎈☕\n"]);
+        assert_eq!(shard("", 2).unwrap(), ["", ""]);
+
+        let error = shard("👶😲🇲👅🍉", 2).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_utf16() {
+        let data: Vec<u8> = (0..64).collect();
+        for len in 0..data.len() {
+            let encoded = ::encode_to_string(&mut &data[..len]).unwrap();
+            let units: Vec<u16> = encoded.encode_utf16().collect();
+            let mut output = Vec::new();
+            assert_eq!(decode_utf16(&units, &mut output).unwrap(), len);
+            assert_eq!(output, &data[..len]);
+        }
+
+        // the padding is in the Basic Multilingual Plane and takes a single code unit
+        let mut units: Vec<u16> = "👖📸🎈☕".encode_utf16().collect();
+        assert_eq!(units.len(), 7);
+        units.insert(6, 0xd83d);
+        let error = decode_utf16(&units, &mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "utf16 data contained an unpaired surrogate 0xd83d at offset 6");
+
+        let units: Vec<u16> = "👖📸a☕".encode_utf16().collect();
+        let error = decode_utf16(&units, &mut Vec::new()).unwrap_err();
+        assert_eq!(error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+                   Some(&DecodeError::NotInAlphabet { c: 'a', position: 2 }));
+    }
+
+    #[test]
+    fn test_byte_iter() {
+        let data: Vec<u8> = (0..64).collect();
+        let encoded = ::encode_to_string(&mut data.as_slice()).unwrap();
+
+        let mut output = Vec::new();
+        let interrupted = || io::Error::new(io::ErrorKind::Interrupted, "interrupted");
+        let bytes = encoded.bytes().flat_map(|b| vec![Err(interrupted()), Ok(b)]);
+        assert_eq!(decode_from_byte_iter(bytes, &mut output).unwrap(), data.len());
+        assert_eq!(output, data);
+
+        let truncated = encoded.bytes().take(encoded.len() - 1).map(Ok);
+        let error = decode_from_byte_iter(truncated, &mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let failing = encoded.bytes().take(8).map(Ok).chain(Some(Err(io::Error::other("failure"))));
+        let error = decode_from_byte_iter(failing, &mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Other);
+        assert_eq!(error.to_string(), "Failed to read from the source: failure");
+    }
+
+    #[test]
+    fn test_invalid_utf8_error_details() {
+        let mut input = "👖📸".as_bytes().to_vec();
+        input.extend_from_slice(&[0xF0, 0x9F, 0x41]);
+
+        let error = decode_to_vec(&mut input.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            error.to_string(),
+            "byte stream did not contain valid utf8: invalid byte sequence [f0, 9f, 41] at offset 8"
+        );
+
+        let error = decode_from_byte_iter(input.iter().cloned().take(10).map(Ok), &mut Vec::new()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "byte stream did not contain valid utf8: invalid byte sequence [f0, 9f] at offset 8"
+        );
+
+        let error = decode_to_vec(&mut &[0xFE][..]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "byte stream did not contain valid utf8: invalid byte sequence [fe] at offset 0"
+        );
+    }
+
+    #[test]
+    fn test_fixed_segments() {
+        let input: String = [
+            EMOJIS[687], EMOJIS[222], EMOJIS[960], EMOJIS[291],
+            EMOJIS[0], EMOJIS[16], PADDING, PADDING,
+            EMOJIS[('k' as usize) << 2], PADDING, PADDING, PADDING,
+        ].iter().cloned().collect();
+
+        let segments = decode_fixed_segments(&mut input.as_bytes(), &[2, 0, 1]).unwrap();
+        assert_eq!(segments, vec![vec![0xAB, 0xCD, 0xEF, 0x01, 0x23, 0, 1], vec![], b"k".to_vec()]);
+
+        let mut source = input.as_bytes();
+        let segments = decode_fixed_segments(&mut source, &[1]).unwrap();
+        assert_eq!(segments, vec![vec![0xAB, 0xCD, 0xEF, 0x01, 0x23]]);
+        assert_eq!(decode_to_vec(&mut source).unwrap(), b"\x00\x01k");
+
+        let error = decode_fixed_segments(&mut input.as_bytes(), &[2, 2]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_ecoji_eq() {
+        let abc = ::encode_to_string(&mut &b"abc"[..]).unwrap();
+        let ab_c = ::encode_to_string(&mut &b"ab"[..]).unwrap() + &::encode_to_string(&mut &b"c"[..]).unwrap();
+        assert!(ecoji_eq(&abc, &ab_c).unwrap());
+        assert!(ecoji_eq(&abc, &format!(" {}\r\n", abc)).unwrap());
+        assert!(ecoji_eq("", "\n").unwrap());
+        assert!(!ecoji_eq(&abc, &::encode_to_string(&mut &b"ab"[..]).unwrap()).unwrap());
+
+        // different, but decodable to the same data
+        let k = [EMOJIS[('k' as usize) << 2 | 1], PADDING, PADDING, PADDING].iter().cloned().collect::<String>();
+        assert!(ecoji_eq(&k, &::encode_to_string(&mut &b"k"[..]).unwrap()).unwrap());
+
+        assert!(ecoji_eq(&abc, "not ecoji data").is_err());
+    }
+
+    #[test]
+    fn test_reference_wrapped_input() {
+        let data: Vec<u8> = (0..=255).collect();
+        let encoded = ::encode_to_string(&mut data.as_slice()).unwrap();
+
+        // the reference tool wraps lines at 76 code points and ends the last line with a newline
+        let chars: Vec<_> = encoded.chars().collect();
+        let mut wrapped = String::new();
+        for line in chars.chunks(76) {
+            wrapped.extend(line);
+            wrapped.push('\n');
+        }
+
+        assert_eq!(decode_to_vec(&mut wrapped.as_bytes()).unwrap(), data);
+        assert_eq!(decode_to_vec(&mut wrapped.replace('\n', "\r\n").as_bytes()).unwrap(), data);
+    }
+
+    #[test]
+    fn test_groups() {
+        let input: String = [
+            EMOJIS[687], EMOJIS[222], EMOJIS[960], EMOJIS[291],
+            EMOJIS[0], EMOJIS[16], PADDING, PADDING,
+        ].iter().cloned().collect();
+
+        let groups: Vec<_> = decode_groups(input.as_bytes()).map(Result::unwrap).collect();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(&*groups[0], &[0xAB, 0xCD, 0xEF, 0x01, 0x23]);
+        assert_eq!(&*groups[1], &[0, 1]);
+    }
+
+    #[test]
+    fn test_groups_stop_after_error() {
+        let input: String = [EMOJIS[0], EMOJIS[16], PADDING, PADDING, EMOJIS[0]].iter().cloned().collect();
+
+        let mut groups = decode_groups(input.as_bytes());
+        assert_eq!(&*groups.next().unwrap().unwrap(), &[0, 1]);
+        assert_eq!(groups.next().unwrap().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert!(groups.next().is_none());
     }
 }
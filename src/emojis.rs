@@ -1,10 +1,6 @@
-include!(concat!(env!("OUT_DIR"), "/emojis.rs"));
-
-pub fn is_valid_alphabet_char(c: char) -> bool {
-    [PADDING, PADDING_40, PADDING_41, PADDING_42, PADDING_43].contains(&c) ||
-        EMOJIS_REV.contains_key(&c)
-}
+pub use ecoji_alphabet::*;
 
+#[cfg(feature = "phf")]
 #[test]
 fn test_mapping() {
     assert_eq!(EMOJIS.len(), 1024);
@@ -13,3 +9,80 @@ fn test_mapping() {
         assert_eq!(i, EMOJIS_REV[&c]);
     }
 }
+
+#[cfg(feature = "phf")]
+#[test]
+fn test_safe_mapping() {
+    assert_eq!(safe::EMOJIS.len(), 1024);
+    assert_eq!(safe::EMOJIS_REV.len(), 1024);
+    for (i, c) in safe::EMOJIS.iter().cloned().enumerate() {
+        assert_eq!(i, safe::EMOJIS_REV[&c]);
+    }
+}
+
+/// Checks that the generated tables make a working codec: every code point of an alphabet
+/// decodes to the bits it encodes, and every kind of padding round-trips.
+#[test]
+fn test_round_trips() {
+    use alphabet::Alphabet;
+    use options::{DecodeOptions, EncodeOptions};
+
+    let tables: [(Alphabet, &[char; 1024], [char; 5]); 2] = [
+        (Alphabet::STANDARD, &EMOJIS, [PADDING, PADDING_40, PADDING_41, PADDING_42, PADDING_43]),
+        (Alphabet::SAFE, &safe::EMOJIS,
+         [safe::PADDING, safe::PADDING_40, safe::PADDING_41, safe::PADDING_42, safe::PADDING_43]),
+    ];
+
+    for &(alphabet, emojis, paddings) in &tables {
+        let encode = |data: &[u8]| EncodeOptions::new().alphabet(alphabet).encode_to_string(&mut &data[..]).unwrap();
+        let decode = |input: &str| DecodeOptions::new().alphabet(alphabet).decode_to_vec(&mut input.as_bytes()).unwrap();
+
+        // The values 0 to 1023 packed into 10 bits each encode to the whole alphabet, in order
+        let mut data = vec![0u8; 1024 * 10 / 8];
+        for value in 0..1024 {
+            for bit in 0..10 {
+                if value >> (9 - bit) & 1 == 1 {
+                    let offset = value * 10 + bit;
+                    data[offset / 8] |= 0x80 >> (offset % 8);
+                }
+            }
+        }
+        let encoded = encode(&data);
+        assert!(encoded.chars().eq(emojis.iter().cloned()));
+        assert_eq!(decode(&encoded), data);
+
+        // 1 to 4 bytes end with paddings; the low bits of the fourth byte select the 4-byte padding
+        for len in 1..5 {
+            for low_bits in 0..4 {
+                let data: Vec<u8> = [0xa5, 0x5a, 0xc3, 0x3c | low_bits][..len].to_vec();
+                let encoded: Vec<char> = encode(&data).chars().collect();
+                let expected_padding = if len == 4 { paddings[1 + low_bits as usize] } else { paddings[0] };
+                assert_eq!(encoded[3], expected_padding);
+                assert_eq!(decode(&encoded.iter().collect::<String>()), data);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_utf8_widths() {
+    use alphabet::{MAX_EMOJI_UTF8_LEN as CRATE_MAX, MIN_EMOJI_UTF8_LEN as CRATE_MIN};
+
+    let tables: [(&[char], [char; 5], usize, usize); 2] = [
+        (&EMOJIS, [PADDING, PADDING_40, PADDING_41, PADDING_42, PADDING_43],
+         MIN_EMOJI_UTF8_LEN, MAX_EMOJI_UTF8_LEN),
+        (&safe::EMOJIS, [safe::PADDING, safe::PADDING_40, safe::PADDING_41, safe::PADDING_42, safe::PADDING_43],
+         safe::MIN_EMOJI_UTF8_LEN, safe::MAX_EMOJI_UTF8_LEN),
+    ];
+
+    for &(emojis, paddings, min, max) in &tables {
+        let widths: Vec<_> = emojis.iter().chain(&paddings).map(|c| c.len_utf8()).collect();
+        assert_eq!(widths.len(), 1029);
+        assert_eq!(widths.iter().cloned().min(), Some(min));
+        assert_eq!(widths.iter().cloned().max(), Some(max));
+        assert!(CRATE_MIN <= min && max <= CRATE_MAX);
+    }
+
+    // the current tables mix 3-byte and 4-byte code points
+    assert_eq!((CRATE_MIN, CRATE_MAX), (3, 4));
+}
@@ -1,8 +1,21 @@
-use emojis::*;
-use std::io::{self, Read, Write};
+#[cfg(feature = "fast-encode")]
+use alphabet::BITS_PER_CHAR;
+use alphabet::{Alphabet, BYTES_PER_GROUP, CHARS_PER_GROUP, MAX_EMOJI_UTF8_LEN};
+use error::EncodeError;
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, BufRead, Cursor, Read, Write};
+use std::sync::mpsc::Receiver;
 
-fn encode_chunk<W: Write + ?Sized>(s: &[u8], out: &mut W) -> io::Result<usize> {
-    assert!(s.len() > 0 && s.len() <= 5, "Unexpected slice length");
+/// Encodes a single chunk of input bytes as one group of four code points.
+///
+/// The caller must guarantee that the chunk contains from 1 to 5 bytes; this is only checked
+/// in debug builds. All callers obtain chunks by reading into a 5-byte buffer and skip the
+/// encoding step when nothing was read, which upholds this contract.
+fn encode_group(alphabet: &Alphabet, s: &[u8]) -> [char; CHARS_PER_GROUP] {
+    debug_assert!(!s.is_empty() && s.len() <= BYTES_PER_GROUP, "Unexpected slice length");
 
     let (b0, b1, b2, b3, b4) = (
         s[0] as usize,
@@ -12,78 +25,211 @@ fn encode_chunk<W: Write + ?Sized>(s: &[u8], out: &mut W) -> io::Result<usize> {
         s.get(4).cloned().unwrap_or(0) as usize,
     );
 
-    let mut chars = [
-        EMOJIS[b0 << 2 | b1 >> 6] as char,
-        PADDING,
-        PADDING,
-        PADDING,
+    let values = [
+        b0 << 2 | b1 >> 6,
+        (b1 & 0x3f) << 4 | b2 >> 4,
+        (b2 & 0x0f) << 6 | b3 >> 2,
+        (b3 & 0x03) << 8 | b4,
     ];
 
-    match s.len() {
-        1 => {}
-        2 => {
-            chars[1] = EMOJIS[(b1 & 0x3f) << 4 | b2 >> 4]
-        }
-        3 => {
-            chars[1] = EMOJIS[(b1 & 0x3f) << 4 | b2 >> 4];
-            chars[2] = EMOJIS[(b2 & 0x0f) << 6 | b3 >> 2];
-        }
-        4 => {
-            chars[1] = EMOJIS[(b1 & 0x3f) << 4 | b2 >> 4];
-            chars[2] = EMOJIS[(b2 & 0x0f) << 6 | b3 >> 2];
+    let mut chars = [alphabet.padding(); CHARS_PER_GROUP];
+    for ((c, &kind), &value) in chars.iter_mut().zip(&expected_padding(s.len())).zip(&values) {
+        *c = match kind {
+            PaddingKind::Data => alphabet.emoji(value),
+            PaddingKind::Padding => alphabet.padding(),
+            PaddingKind::Padding4x => alphabet.padding_4x(b3 & 0x03),
+        };
+    }
 
-            chars[3] = match b3 & 0x03 {
-                0 => PADDING_40,
-                1 => PADDING_41,
-                2 => PADDING_42,
-                3 => PADDING_43,
-                _ => unreachable!(),
-            }
-        }
-        5 => {
-            chars[1] = EMOJIS[(b1 & 0x3f) << 4 | b2 >> 4];
-            chars[2] = EMOJIS[(b2 & 0x0f) << 6 | b3 >> 2];
-            chars[3] = EMOJIS[(b3 & 0x03) << 8 | b4];
+    chars
+}
+
+/// Encodes a single chunk of input bytes and writes the resulting group to `out`.
+///
+/// The same contract as for `encode_group` applies to the chunk length.
+fn encode_chunk<W: Write + ?Sized>(alphabet: &Alphabet, s: &[u8], out: &mut W) -> io::Result<usize> {
+    let mut buf = [0; CHARS_PER_GROUP * MAX_EMOJI_UTF8_LEN];
+    let len = encode_chunk_into(alphabet, s, &mut buf);
+    out.write_all(&buf[..len]).map_err(EncodeError::wrap_destination)?;
+
+    Ok(len)
+}
+
+/// Encodes a single chunk of input bytes into the UTF-8 representation of the resulting group
+/// and returns its length.
+///
+/// The same contract as for `encode_group` applies to the chunk length.
+fn encode_chunk_into(alphabet: &Alphabet, s: &[u8], buf: &mut [u8; CHARS_PER_GROUP * MAX_EMOJI_UTF8_LEN]) -> usize {
+    #[cfg(feature = "fast-encode")]
+    {
+        if s.len() == BYTES_PER_GROUP {
+            return encode_complete_chunk_into(alphabet, s, buf);
         }
-        _ => unreachable!(),
     }
+    group_into_utf8(&encode_group(alphabet, s), buf)
+}
 
-    let mut buf = [0; 4];
-    let mut bytes_written = 0;
-    for c in chars.iter() {
-        let s = c.encode_utf8(&mut buf).as_bytes();
-        out.write_all(s)?;
-        bytes_written += s.len();
+/// Encodes a complete chunk of input bytes like `encode_chunk_into`, but copies the UTF-8
+/// representations of the code points from the precomputed table of the alphabet instead of
+/// building the group and encoding its code points one by one.
+///
+/// A complete group never contains paddings, so every code point is a single table lookup.
+#[cfg(feature = "fast-encode")]
+fn encode_complete_chunk_into(alphabet: &Alphabet, s: &[u8],
+                              buf: &mut [u8; CHARS_PER_GROUP * MAX_EMOJI_UTF8_LEN]) -> usize {
+    let chunk = s.iter().fold(0, |chunk, &b| chunk << 8 | u64::from(b));
+    let mut len = 0;
+    for i in (0..CHARS_PER_GROUP).rev() {
+        let bits = (chunk >> (i * BITS_PER_CHAR)) as usize & ((1 << BITS_PER_CHAR) - 1);
+        let &(ref utf8, n) = alphabet.emoji_utf8(bits);
+        buf[len..len + MAX_EMOJI_UTF8_LEN].copy_from_slice(utf8);
+        len += n;
     }
+    len
+}
 
-    Ok(bytes_written)
+/// Writes the UTF-8 representation of an encoded group to `out` and returns its length.
+fn write_group<W: Write + ?Sized>(chars: &[char; CHARS_PER_GROUP], out: &mut W) -> io::Result<usize> {
+    let mut buf = [0; CHARS_PER_GROUP * MAX_EMOJI_UTF8_LEN];
+    let len = group_into_utf8(chars, &mut buf);
+    out.write_all(&buf[..len]).map_err(EncodeError::wrap_destination)?;
+
+    Ok(len)
+}
+
+/// Stores the UTF-8 representation of an encoded group into the buffer and returns its length.
+fn group_into_utf8(chars: &[char; CHARS_PER_GROUP], buf: &mut [u8; CHARS_PER_GROUP * MAX_EMOJI_UTF8_LEN]) -> usize {
+    let mut len = 0;
+    for c in chars.iter() {
+        len += c.encode_utf8(&mut buf[len..]).len();
+    }
+    len
 }
 
-fn read_exact<R: Read + ?Sized>(source: &mut R, mut buf: &mut [u8]) -> io::Result<usize> {
+/// Fills the buffer from the source, stopping early only at EOF, and returns the number of bytes
+/// read.
+fn read_exact<R: Read + ?Sized>(source: &mut R, buf: &mut [u8; BYTES_PER_GROUP]) -> io::Result<usize> {
     let mut bytes_read = 0;
-    while !buf.is_empty() {
-        match source.read(buf) {
+    while bytes_read < buf.len() {
+        match source.read(&mut buf[bytes_read..]) {
             Ok(0) => break,
-            Ok(n) => {
-                let tmp = buf;
-                buf = &mut tmp[n..];
-                bytes_read += n;
-            }
+            Ok(n) => bytes_read += n,
             Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
-            Err(e) => return Err(e),
+            Err(e) => return Err(EncodeError::wrap_source(e)),
         }
     }
     Ok(bytes_read)
 }
 
+/// The core of the incremental encoders: a state machine which collects input bytes into groups
+/// and produces the code points of a group once it is complete.
+///
+/// Input is passed to [`feed`](#method.feed), which returns the number of consumed bytes and
+/// stops at the end of the current group. Once the group is complete, its code points are taken
+/// with [`take_group`](#method.take_group), and at the end of the input the last, possibly
+/// incomplete, group is taken with [`finish`](#method.finish).
+///
+/// The state performs no I/O by itself. The reading encoders, `encode` and `EncodingReader`,
+/// drive it with [`read_group`](fn.read_group.html), and the encoders which receive their input
+/// in pieces, whether they are writers or are pulled from, feed it directly; an asynchronous
+/// front-end can drive it in the same way.
+#[derive(Debug, Clone)]
+struct EncodeState {
+    alphabet: Alphabet,
+    buf: [u8; BYTES_PER_GROUP],
+    len: usize,
+}
+
+impl EncodeState {
+    fn new(alphabet: &Alphabet) -> EncodeState {
+        EncodeState {
+            alphabet: *alphabet,
+            buf: [0; BYTES_PER_GROUP],
+            len: 0,
+        }
+    }
+
+    /// Appends input bytes to the current group, stopping at its end, and returns the number of
+    /// consumed bytes. Nothing is consumed while the group is complete.
+    fn feed(&mut self, data: &[u8]) -> usize {
+        let n = cmp::min(self.buf.len() - self.len, data.len());
+        self.buf[self.len..self.len + n].copy_from_slice(&data[..n]);
+        self.len += n;
+        n
+    }
+
+    /// Returns the number of bytes in the current group.
+    fn buffered(&self) -> usize {
+        self.len
+    }
+
+    /// Checks whether the current group is complete, i.e. whether it must be emitted before more
+    /// bytes can be fed.
+    fn is_full(&self) -> bool {
+        self.len == self.buf.len()
+    }
+
+    /// Returns the code points of the current group without removing it, or `None` if the group
+    /// is empty. An incomplete group is padded, so this must only be emitted at the end of input.
+    fn group(&self) -> Option<[char; CHARS_PER_GROUP]> {
+        if self.len > 0 {
+            Some(encode_group(&self.alphabet, &self.buf[..self.len]))
+        } else {
+            None
+        }
+    }
+
+    /// Removes the current group after it has been emitted.
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Returns the code points of the current group, like [`group`](#method.group), and removes it.
+    fn take_group(&mut self) -> Option<[char; CHARS_PER_GROUP]> {
+        let group = self.group();
+        self.clear();
+        group
+    }
+
+    /// Ends the input: returns the code points of the last, possibly incomplete, group, or `None`
+    /// if there are no bytes left, and leaves the state empty.
+    fn finish(&mut self) -> Option<[char; CHARS_PER_GROUP]> {
+        self.take_group()
+    }
+}
+
+/// Reads from the source into the state until the current group is complete or the source is
+/// exhausted, and returns whether the group is complete.
+///
+/// Bytes are kept in the state as soon as they are read, so a failed read, e.g. with
+/// `WouldBlock`, can be retried without losing a part of the group.
+fn read_group<R: Read + ?Sized>(state: &mut EncodeState, source: &mut R) -> io::Result<bool> {
+    while !state.is_full() {
+        // Reading directly into the group buffer is the same as feeding the bytes, without copying
+        match source.read(&mut state.buf[state.len..]) {
+            Ok(0) => return Ok(false),
+            Ok(n) => state.len += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(EncodeError::wrap_source(e)),
+        }
+    }
+    Ok(true)
+}
+
 /// Encodes the entire source into the Ecoji format and writes a UTF-8 representation of
 /// the encoded data to the provided destination.
 ///
+/// Every five bytes of the input are encoded as a group of four code points. Only the last group
+/// may be incomplete, in which case it is padded; if the input length is a multiple of five,
+/// the output consists of complete groups only and contains no padding at all.
+///
 /// If successful, returns the number of bytes which were written to the destination writer.
 ///
 /// Returns an error when either source or destination operation has failed. No guarantees are
 /// made about the state of the destination if an error occurs, so it is possible for the
-/// destination to contain only a part of the encoded data.
+/// destination to contain only a part of the encoded data. Such errors keep the kind of the
+/// original error and wrap [`EncodeError::SourceIo`](enum.EncodeError.html) or
+/// [`EncodeError::DestIo`](enum.EncodeError.html), depending on which operation failed.
 ///
 /// # Examples
 ///
@@ -102,10 +248,172 @@ fn read_exact<R: Read + ?Sized>(source: &mut R, mut buf: &mut [u8]) -> io::Resul
 /// # test().unwrap();
 /// ```
 pub fn encode<R: Read + ?Sized, W: Write + ?Sized>(source: &mut R, destination: &mut W) -> io::Result<usize> {
-    let mut buf = [0; 5];
+    encode_up_to(&Alphabet::STANDARD, source, destination, None)
+}
+
+/// Encodes the entire source into the Ecoji format, writes a UTF-8 representation of the encoded
+/// data to the provided destination and flushes it.
+///
+/// This is the same as [`encode`](fn.encode.html) followed by a call to `flush` on the
+/// destination. It is convenient for buffered destinations like `BufWriter`, which otherwise
+/// keep the last part of the output in their buffer until they are flushed or dropped, and
+/// which ignore errors when dropped.
+///
+/// If successful, returns the number of bytes which were written to the destination writer.
+///
+/// In addition to the [`encode`](fn.encode.html) failure conditions, returns an error when
+/// flushing the destination fails.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::BufWriter;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let mut output = BufWriter::new(Vec::new());
+/// ecoji::encode_and_flush(&mut "input data".as_bytes(), &mut output)?;
+///
+/// assert_eq!(output.get_ref(), "👶😲🇲👅🍉🔙🌥🌩".as_bytes());
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn encode_and_flush<R: Read + ?Sized, W: Write + ?Sized>(source: &mut R, destination: &mut W) -> io::Result<usize> {
+    let bytes_written = encode(source, destination)?;
+    destination.flush().map_err(EncodeError::wrap_destination)?;
+    Ok(bytes_written)
+}
+
+/// Encodes the entire source into the Ecoji format and writes a UTF-8 representation of
+/// the encoded data to each of the provided destinations.
+///
+/// This is meant for sending the same output to several places at once, e.g. to a file and
+/// a socket, without encoding the data twice or keeping the whole output to replay it. Every
+/// piece of the output is written to all destinations, in the order of the slice, before
+/// the next piece is encoded. The destinations are not flushed.
+///
+/// If successful, returns the number of bytes which were written to each destination writer.
+///
+/// Failure conditions are the same as those of the [`encode`](fn.encode.html) function; encoding
+/// stops at the first destination which fails. Since the destinations are written to one after
+/// another, those preceding the failed one may have received a part of the output which
+/// the others have not.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Write;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let mut file: Vec<u8> = Vec::new();
+/// let mut socket: Vec<u8> = Vec::new();
+/// ecoji::encode_tee(&mut "input data".as_bytes(), &mut [&mut file as &mut dyn Write, &mut socket])?;
+///
+/// assert_eq!(file, "👶😲🇲👅🍉🔙🌥🌩".as_bytes());
+/// assert_eq!(socket, file);
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn encode_tee<R: Read + ?Sized>(source: &mut R, destinations: &mut [&mut dyn Write]) -> io::Result<usize> {
+    encode_up_to(&Alphabet::STANDARD, source, &mut TeeWriter(destinations), None)
+}
+
+/// A writer which writes everything to all of the wrapped writers.
+struct TeeWriter<'a, 'b: 'a>(&'a mut [&'b mut dyn Write]);
+
+impl<'a, 'b> Write for TeeWriter<'a, 'b> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for destination in self.0.iter_mut() {
+            destination.write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for destination in self.0.iter_mut() {
+            destination.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Encodes the entire source into the Ecoji format and writes a UTF-8 representation of
+/// the encoded data to the provided destination, failing if the source contains more than
+/// `max_input_bytes` bytes.
+///
+/// This function is meant for encoding data of untrusted size: encoding stops as soon as more
+/// than `max_input_bytes` bytes are read from the source. Only the complete groups preceding
+/// the group which crossed the limit will have been written to the destination; the bytes of
+/// that group are not written, even those within the limit, so the destination holds a valid
+/// encoding of a prefix of the input whose length is a multiple of five.
+///
+/// If successful, returns the number of bytes which were written to the destination writer.
+///
+/// In addition to the [`encode`](fn.encode.html) failure conditions, this function also returns
+/// an error of the `InvalidData` kind wrapping
+/// [`EncodeError::InputTooLarge`](enum.EncodeError.html) if the limit is exceeded.
+///
+/// # Examples
+///
+/// ```
+/// use ecoji::EncodeError;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let input = "input data";
+///
+/// let mut output: Vec<u8> = Vec::new();
+/// ecoji::encode_limited(&mut input.as_bytes(), &mut output, 10)?;
+/// assert_eq!(output, "👶😲🇲👅🍉🔙🌥🌩".as_bytes());
+///
+/// let error = ecoji::encode_limited(&mut input.as_bytes(), &mut Vec::new(), 9).unwrap_err();
+/// assert_eq!(
+///     error.get_ref().and_then(|e| e.downcast_ref::<EncodeError>()),
+///     Some(&EncodeError::InputTooLarge { limit: 9 })
+/// );
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn encode_limited<R: Read + ?Sized, W: Write + ?Sized>(source: &mut R, destination: &mut W,
+                                                          max_input_bytes: usize) -> io::Result<usize> {
+    encode_up_to(&Alphabet::STANDARD, source, destination, Some(max_input_bytes))
+}
+
+/// Encodes the source into the Ecoji format and writes a UTF-8 representation of the encoded
+/// data to the provided destination, stopping early if the given callback returns `false`.
+///
+/// The callback is invoked before each group of input bytes is read, so a long-running encoding
+/// can be cancelled from the callback, e.g. by checking a flag set by another thread. When it is
+/// cancelled, everything written to the destination so far is a sequence of complete groups,
+/// which is a valid prefix of the full encoded data.
+///
+/// Returns the number of bytes which were written to the destination writer, both when the
+/// encoding completes and when it is cancelled. Failure conditions are exactly the same as
+/// those of the [`encode`](fn.encode.html) function.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let mut groups_left = 1;
+/// let mut output: Vec<u8> = Vec::new();
+/// ecoji::encode_cancellable(&mut "input data".as_bytes(), &mut output, &mut || {
+///     groups_left -= 1;
+///     groups_left >= 0
+/// })?;
+///
+/// assert_eq!(output, "👶😲🇲👅".as_bytes());
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn encode_cancellable<R: Read + ?Sized, W: Write + ?Sized>(source: &mut R, destination: &mut W,
+                                                              should_continue: &mut dyn FnMut() -> bool) -> io::Result<usize> {
+    let mut buf = [0; BYTES_PER_GROUP];
     let mut bytes_written = 0;
 
-    loop {
+    while should_continue() {
         let n = read_exact(source, &mut buf)?;
 
         // EOF
@@ -113,87 +421,1703 @@ pub fn encode<R: Read + ?Sized, W: Write + ?Sized>(source: &mut R, destination:
             break;
         }
 
-        bytes_written += encode_chunk(&buf[..n], destination)?;
+        bytes_written += encode_chunk(&Alphabet::STANDARD, &buf[..n], destination)?;
     }
 
     Ok(bytes_written)
 }
 
-/// Encodes the entire source into the Ecoji format, storing the result of the encoding to a
-/// new owned string.
+pub(crate) fn encode_up_to<R: Read + ?Sized, W: Write + ?Sized>(alphabet: &Alphabet, source: &mut R,
+                                                               destination: &mut W,
+                                                               max_input_bytes: Option<usize>) -> io::Result<usize> {
+    let mut state = EncodeState::new(alphabet);
+    let mut bytes_read = 0;
+    let mut bytes_written = 0;
+    // The last complete chunk and its encoding, which is reused for runs of identical chunks,
+    // like in zero-filled data
+    let mut last_chunk = None;
+    let mut last_group = [0; CHARS_PER_GROUP * MAX_EMOJI_UTF8_LEN];
+    let mut last_group_len = 0;
+
+    loop {
+        let complete = read_group(&mut state, source)?;
+
+        bytes_read += state.buffered();
+        if let Some(limit) = max_input_bytes {
+            if bytes_read > limit {
+                return Err(EncodeError::InputTooLarge { limit }.into_io());
+            }
+        }
+
+        // EOF
+        if !complete {
+            break;
+        }
+
+        // Comparing chunks as integers is considerably faster than comparing 5-byte arrays
+        let chunk = state.buf.iter().fold(0, |chunk, &b| chunk << 8 | u64::from(b));
+        if last_chunk != Some(chunk) {
+            last_group_len = encode_chunk_into(alphabet, &state.buf, &mut last_group);
+            last_chunk = Some(chunk);
+        }
+        state.clear();
+        destination.write_all(&last_group[..last_group_len]).map_err(EncodeError::wrap_destination)?;
+        bytes_written += last_group_len;
+    }
+
+    if let Some(group) = state.finish() {
+        bytes_written += write_group(&group, destination)?;
+    }
+
+    Ok(bytes_written)
+}
+
+/// Encodes a sequence of byte slices into the Ecoji format as if they were one contiguous input,
+/// and writes a UTF-8 representation of the encoded data to the provided destination.
 ///
-/// Returns a string with the encoded data if successful.
+/// This function avoids concatenating non-contiguous buffers before encoding. Groups of five
+/// bytes may span several slices, so the result is exactly the same as the output of
+/// [`encode`](fn.encode.html) for the concatenation of all slices.
 ///
-/// Failure conditions are exactly the same as those of the [`encode`](fn.encode.html) function;
-/// because the encoding output is always a valid sequence of emoji code points, it is guaranteed
-/// to be representable as a valid UTF-8 sequence.
+/// If successful, returns the number of bytes which were written to the destination writer.
 ///
-/// # Examples
+/// Returns an error when the destination operation has failed. No guarantees are made about the
+/// state of the destination if an error occurs.
 ///
-/// Successful encoding:
+/// # Examples
 ///
 /// ```
 /// # fn test() -> ::std::io::Result<()> {
-/// let input = "input data";
-/// let output: String = ecoji::encode_to_string(&mut input.as_bytes())?;
+/// let mut output: Vec<u8> = Vec::new();
+/// ecoji::encode_vectored(&[b"inp", b"ut d", b"ata"], &mut output)?;
 ///
-/// assert_eq!(output, "👶😲🇲👅🍉🔙🌥🌩");
+/// assert_eq!(output, "👶😲🇲👅🍉🔙🌥🌩".as_bytes());
 /// #  Ok(())
 /// # }
 /// # test().unwrap();
 /// ```
-pub fn encode_to_string<R: Read + ?Sized>(source: &mut R) -> io::Result<String> {
-    let mut output = Vec::new();
-    encode(source, &mut output)?;
-    // encoded output is guaranteed to be valid UTF-8
-    Ok(unsafe { String::from_utf8_unchecked(output) })
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub fn encode_vectored<W: Write + ?Sized>(inputs: &[&[u8]], destination: &mut W) -> io::Result<usize> {
+    let mut state = EncodeState::new(&Alphabet::STANDARD);
+    let mut bytes_written = 0;
 
-    fn check(input: &[u8], output: &[u8]) {
-        let buf = encode_to_string(&mut input.clone()).unwrap();
-        assert_eq!(output, buf.as_bytes());
+    for &input in inputs {
+        bytes_written += encode_piece(&mut state, input, destination)?;
     }
 
-    fn check_chars(input: &[u8], output: &[char]) {
-        let buf = encode_to_string(&mut input.clone()).unwrap();
-        let chars: Vec<_> = buf.chars().collect();
-        assert_eq!(output, chars.as_slice());
+    if let Some(group) = state.finish() {
+        bytes_written += write_group(&group, destination)?;
     }
 
-    #[test]
-    fn test_random() {
-        check(b"abc", "👖📸🎈☕".as_bytes());
+    Ok(bytes_written)
+}
+
+/// Encodes chunks of bytes received from the channel into the Ecoji format as one continuous
+/// stream, and writes a UTF-8 representation of the encoded data to the provided destination.
+///
+/// This decouples producing the data from encoding it: the chunks may be sent from other
+/// threads, and encoding finishes when all senders are dropped. Like with
+/// [`encode_vectored`](fn.encode_vectored.html), groups of five bytes may span several chunks,
+/// so the result is exactly the same as the output of [`encode`](fn.encode.html) for
+/// the concatenation of all chunks. Each chunk is written to the destination as soon as it is
+/// received, except for up to four trailing bytes, which are kept until the next chunk completes
+/// their group.
+///
+/// If successful, returns the number of bytes which were written to the destination writer.
+///
+/// Returns an error when the destination operation has failed. No guarantees are made about the
+/// state of the destination if an error occurs. The receiver is dropped in this case, so the
+/// senders get errors on subsequent sends.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::mpsc;
+/// use std::thread;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let (tx, rx) = mpsc::channel();
+/// let producer = thread::spawn(move || {
+///     for chunk in &["inp", "ut d", "ata"] {
+///         tx.send(chunk.as_bytes().to_vec()).unwrap();
+///     }
+/// });
+///
+/// let mut output: Vec<u8> = Vec::new();
+/// ecoji::encode_channel(rx, &mut output)?;
+/// producer.join().unwrap();
+///
+/// assert_eq!(output, "👶😲🇲👅🍉🔙🌥🌩".as_bytes());
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn encode_channel<W: Write + ?Sized>(rx: Receiver<Vec<u8>>, destination: &mut W) -> io::Result<usize> {
+    let mut state = EncodeState::new(&Alphabet::STANDARD);
+    let mut bytes_written = 0;
+
+    for chunk in rx {
+        bytes_written += encode_piece(&mut state, &chunk, destination)?;
     }
 
-    #[test]
-    fn test_one_byte() {
-        check_chars(b"k", &[EMOJIS[('k' as usize) << 2], PADDING, PADDING, PADDING]);
+    if let Some(group) = state.finish() {
+        bytes_written += write_group(&group, destination)?;
     }
 
-    #[test]
-    fn test_two_bytes() {
-        check_chars(&[0, 1], &[EMOJIS[0], EMOJIS[16], PADDING, PADDING]);
+    Ok(bytes_written)
+}
+
+/// Encodes a piece of a contiguous input, continuing the group started by the previous pieces,
+/// and keeps the bytes of an incomplete trailing group in the state. Returns the number of bytes
+/// written.
+fn encode_piece<W: Write + ?Sized>(state: &mut EncodeState, input: &[u8], destination: &mut W) -> io::Result<usize> {
+    let mut input = input;
+    let mut bytes_written = 0;
+
+    // Complete the group started by the previous pieces, if any
+    if state.buffered() > 0 {
+        input = &input[state.feed(input)..];
+        if state.is_full() {
+            bytes_written += write_group(&state.take_group().unwrap(), destination)?;
+        }
     }
 
-    #[test]
-    fn test_three_bytes() {
-        check_chars(&[0, 1, 2], &[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING]);
+    let mut chunks = input.chunks_exact(BYTES_PER_GROUP);
+    for chunk in &mut chunks {
+        bytes_written += encode_chunk(&state.alphabet, chunk, destination)?;
     }
+    state.feed(chunks.remainder());
 
-    #[test]
-    fn test_four_bytes() {
-        check_chars(&[0, 1, 2, 0], &[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING_40]);
-        check_chars(&[0, 1, 2, 1], &[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING_41]);
-        check_chars(&[0, 1, 2, 2], &[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING_42]);
-        check_chars(&[0, 1, 2, 3], &[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING_43]);
+    Ok(bytes_written)
+}
+
+/// Encodes the given bytes into the Ecoji format, passing each code point of the encoded data to
+/// the provided closure.
+///
+/// This is meant for sinks which work with characters rather than with UTF-8 bytes, like a
+/// terminal cell grid; no UTF-8 encoding is performed. The closure receives exactly the code
+/// points of the output of [`encode`](fn.encode.html), in order.
+///
+/// # Examples
+///
+/// ```
+/// let mut output: Vec<char> = Vec::new();
+/// ecoji::encode_chars_to(b"input data", |c| output.push(c));
+///
+/// assert_eq!(output, "👶😲🇲👅🍉🔙🌥🌩".chars().collect::<Vec<_>>());
+/// ```
+pub fn encode_chars_to<F: FnMut(char)>(input: &[u8], mut f: F) {
+    for chunk in input.chunks(BYTES_PER_GROUP) {
+        for &c in &encode_group(&Alphabet::STANDARD, chunk) {
+            f(c);
+        }
     }
+}
 
-    #[test]
-    fn test_five_bytes() {
-        check_chars(&[0xAB, 0xCD, 0xEF, 0x01, 0x23], &[EMOJIS[687], EMOJIS[222], EMOJIS[960], EMOJIS[291]]);
+/// Encodes the given bytes into the Ecoji format, inserting a separator after every
+/// `every_n_groups` groups, and returns the result as a new string.
+///
+/// Separators make long encoded data easier to read and compare by eye. They are placed between
+/// groups only, never at the end of the output. The separator must not be a code point of the
+/// alphabet, so the output can be decoded by ignoring it with
+/// [`DecodeOptions::ignore_chars`](struct.DecodeOptions.html#method.ignore_chars); line breaks
+/// are ignored by all decoding functions anyway, so a `'\n'` separator needs no special options.
+///
+/// # Panics
+///
+/// Panics if `every_n_groups` is zero or if the separator is a code point of the alphabet,
+/// including the padding code points.
+///
+/// # Examples
+///
+/// ```
+/// use ecoji::DecodeOptions;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let output = ecoji::encode_with_separators(b"input data", 1, '|');
+/// assert_eq!(output, "👶😲🇲👅|🍉🔙🌥🌩");
+///
+/// let decoded = DecodeOptions::new().ignore_chars(&['|']).decode_to_vec(&mut output.as_bytes())?;
+/// assert_eq!(decoded, b"input data");
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn encode_with_separators(input: &[u8], every_n_groups: usize, separator: char) -> String {
+    assert!(every_n_groups > 0, "Separators must be inserted after at least one group");
+    assert!(!Alphabet::STANDARD.contains(separator), "The separator {:?} is a code point of the alphabet", separator);
+
+    let chars_between = every_n_groups * CHARS_PER_GROUP;
+    let separators = encoded_chars(input.len()).saturating_sub(1) / chars_between;
+    let mut output = String::with_capacity(max_encoded_len(input.len()) + separators * separator.len_utf8());
+    let mut count = 0;
+    encode_chars_to(input, |c| {
+        if count > 0 && count % chars_between == 0 {
+            output.push(separator);
+        }
+        output.push(c);
+        count += 1;
+    });
+    output
+}
+
+/// Encodes the given bytes into the Ecoji format, wrapping the output into lines of `width` code
+/// points, and returns the result as a new string.
+///
+/// Lines are separated with `'\n'`, which all decoding functions ignore, so the output can be
+/// decoded directly. There is no line break at the end of the output, and a `width` of zero
+/// disables wrapping. Lines need not end at group boundaries, since line breaks are allowed
+/// anywhere in the encoded data.
+///
+/// Use [`encode_wrapped_with_offsets`](fn.encode_wrapped_with_offsets.html) to also get
+/// the positions of the line breaks.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let output = ecoji::encode_wrapped(b"input data", 3);
+/// assert_eq!(output, "👶😲🇲\n👅🍉🔙\n🌥🌩");
+///
+/// assert_eq!(ecoji::decode_str(&output)?, b"input data");
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn encode_wrapped(input: &[u8], width: usize) -> String {
+    encode_wrapped_into(input, width, None)
+}
+
+/// Encodes the given bytes into the Ecoji format, wrapping the output into lines of `width` code
+/// points, and returns the result together with the offsets of the inserted line breaks.
+///
+/// The output is exactly the same as the output of [`encode_wrapped`](fn.encode_wrapped.html).
+/// Each offset is the index in code points of a `'\n'` in the output, counting the preceding line
+/// breaks too, so the offsets depend only on the width and the length of the input. This lets
+/// a fixed-width display map its positions back to the encoded data: the code point at index `i`
+/// of the output is the code point at index `i - k` of the unwrapped encoding, where `k` is
+/// the number of line breaks before it. See also
+/// [`char_offset_to_input_bytes`](fn.char_offset_to_input_bytes.html) to map those further to
+/// the input bytes.
+///
+/// # Examples
+///
+/// ```
+/// let (output, offsets) = ecoji::encode_wrapped_with_offsets(b"input data", 3);
+/// assert_eq!(output, "👶😲🇲\n👅🍉🔙\n🌥🌩");
+/// assert_eq!(offsets, [3, 7]);
+///
+/// let chars: Vec<char> = output.chars().collect();
+/// assert!(offsets.iter().all(|&i| chars[i] == '\n'));
+/// ```
+pub fn encode_wrapped_with_offsets(input: &[u8], width: usize) -> (String, Vec<usize>) {
+    let mut offsets = Vec::with_capacity(line_breaks(input.len(), width));
+    let output = encode_wrapped_into(input, width, Some(&mut offsets));
+    (output, offsets)
+}
+
+/// Returns the number of line breaks in the output of `encode_wrapped`.
+fn line_breaks(input_len: usize, width: usize) -> usize {
+    match width {
+        0 => 0,
+        _ => encoded_chars(input_len).saturating_sub(1) / width,
+    }
+}
+
+fn encode_wrapped_into(input: &[u8], width: usize, mut offsets: Option<&mut Vec<usize>>) -> String {
+    let mut output = String::with_capacity(max_encoded_len(input.len()) + line_breaks(input.len(), width));
+    let mut count = 0;
+    let mut breaks = 0;
+    encode_chars_to(input, |c| {
+        if width > 0 && count > 0 && count % width == 0 {
+            if let Some(ref mut offsets) = offsets {
+                offsets.push(count + breaks);
+            }
+            output.push('\n');
+            breaks += 1;
+        }
+        output.push(c);
+        count += 1;
+    });
+    output
+}
+
+/// Encodes the entire source into the Ecoji format, wrapping the output into lines of `width`
+/// code points, and writes a UTF-8 representation of the result to the provided destination.
+///
+/// This is the streaming counterpart of
+/// [`encode_wrapped_with_offsets`](fn.encode_wrapped_with_offsets.html): the output is exactly
+/// the same as that of [`encode_wrapped`](fn.encode_wrapped.html) for the data of the source,
+/// and `on_line_break` is called with the offset of each inserted line break as it is written,
+/// instead of collecting the offsets, so memory usage does not depend on the input size.
+///
+/// If successful, returns the number of bytes which were written to the destination writer,
+/// including the line breaks.
+///
+/// Failure conditions are exactly the same as those of the [`encode`](fn.encode.html) function.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let mut output: Vec<u8> = Vec::new();
+/// let mut offsets = Vec::new();
+/// ecoji::encode_wrapped_stream(&mut "input data".as_bytes(), &mut output, 3, &mut |i| offsets.push(i))?;
+///
+/// assert_eq!(output, "👶😲🇲\n👅🍉🔙\n🌥🌩".as_bytes());
+/// assert_eq!(offsets, [3, 7]);
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn encode_wrapped_stream<R: Read + ?Sized, W: Write + ?Sized>(source: &mut R, destination: &mut W, width: usize,
+                                                                 on_line_break: &mut dyn FnMut(usize)) -> io::Result<usize> {
+    let mut writer = WrappingWriter { inner: destination, width, chars: 0, breaks: 0, on_line_break };
+    let bytes_written = encode_up_to(&Alphabet::STANDARD, source, &mut writer, None)?;
+    Ok(bytes_written + writer.breaks)
+}
+
+/// A writer which inserts a line break before every `width` code points of the UTF-8 data
+/// written to it, like `encode_wrapped` does.
+struct WrappingWriter<'a, W: Write + ?Sized + 'a> {
+    inner: &'a mut W,
+    width: usize,
+    chars: usize,
+    breaks: usize,
+    on_line_break: &'a mut dyn FnMut(usize),
+}
+
+impl<'a, W: Write + ?Sized> Write for WrappingWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut start = 0;
+        for (i, &b) in buf.iter().enumerate() {
+            // Continuation bytes never start a code point
+            if b & 0xc0 == 0x80 {
+                continue;
+            }
+            if self.width > 0 && self.chars > 0 && self.chars.is_multiple_of(self.width) {
+                self.inner.write_all(&buf[start..i])?;
+                self.inner.write_all(b"\n")?;
+                (self.on_line_break)(self.chars + self.breaks);
+                self.breaks += 1;
+                start = i;
+            }
+            self.chars += 1;
+        }
+        self.inner.write_all(&buf[start..])?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Encodes the input into the Ecoji format, returning the indices of the encoded code points in
+/// the alphabet instead of the code points themselves.
+///
+/// Data code points have indices from 0 to 1023, which are the 10-bit values they encode. The
+/// padding code points are represented by 1024, and the four paddings which end a 4-byte group
+/// by 1025 to 1028. This allows storing the encoded data compactly, e.g. as packed 11-bit
+/// symbols; use [`decode_from_indices`](fn.decode_from_indices.html) to decode it.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(ecoji::encode_to_indices(b"input data"), [421, 743, 29, 372, 129, 582, 93, 97]);
+/// assert_eq!(ecoji::encode_to_indices(b"abc"), [389, 550, 192, 1024]);
+/// ```
+pub fn encode_to_indices(input: &[u8]) -> Vec<u16> {
+    let alphabet = &Alphabet::STANDARD;
+    let mut indices = Vec::with_capacity(encoded_chars(input.len()));
+    encode_chars_to(input, |c| {
+        let index = alphabet.index_of(c).expect("Encoded character is not in the alphabet");
+        indices.push(index as u16);
+    });
+    indices
+}
+
+/// Encodes the entire source into the Ecoji format, storing the result of the encoding to a
+/// new owned string.
+///
+/// Returns a string with the encoded data if successful.
+///
+/// Failure conditions are exactly the same as those of the [`encode`](fn.encode.html) function;
+/// because the encoding output is always a valid sequence of emoji code points, it is guaranteed
+/// to be representable as a valid UTF-8 sequence.
+///
+/// # Examples
+///
+/// Successful encoding:
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let input = "input data";
+/// let output: String = ecoji::encode_to_string(&mut input.as_bytes())?;
+///
+/// assert_eq!(output, "👶😲🇲👅🍉🔙🌥🌩");
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn encode_to_string<R: Read + ?Sized>(source: &mut R) -> io::Result<String> {
+    encode_to_string_with(&Alphabet::STANDARD, source)
+}
+
+pub(crate) fn encode_to_string_with<R: Read + ?Sized>(alphabet: &Alphabet, source: &mut R) -> io::Result<String> {
+    let mut output = Vec::new();
+    encode_up_to(alphabet, source, &mut output, None)?;
+    // encoded output is guaranteed to be valid UTF-8
+    Ok(unsafe { String::from_utf8_unchecked(output) })
+}
+
+/// Encodes the given bytes into the Ecoji format, storing the result of the encoding to a new
+/// owned string.
+///
+/// The output is the same as the output of [`encode_to_string`](fn.encode_to_string.html). The
+/// difference is that the length of the input is known in advance, so the string is allocated
+/// with [`max_encoded_len`](fn.max_encoded_len.html) bytes of capacity once and is never
+/// reallocated while encoding. Since most code points of the alphabet take four bytes in UTF-8,
+/// the capacity exceeds the length of the result only slightly.
+///
+/// # Examples
+///
+/// ```
+/// let output = ecoji::encode_slice_to_string(b"input data");
+///
+/// assert_eq!(output, "👶😲🇲👅🍉🔙🌥🌩");
+/// assert!(output.len() <= ecoji::max_encoded_len(10));
+/// ```
+pub fn encode_slice_to_string(input: &[u8]) -> String {
+    let mut output = Vec::with_capacity(max_encoded_len(input.len()));
+    encode_append(input, &mut output);
+    // encoded output is guaranteed to be valid UTF-8
+    unsafe { String::from_utf8_unchecked(output) }
+}
+
+/// Encodes the given bytes into the Ecoji format and appends a UTF-8 representation of the
+/// encoded data to the end of the vector.
+///
+/// The existing contents of the vector are kept; to reuse a vector for several encodings, clear
+/// it first. Capacity for [`max_encoded_len`](fn.max_encoded_len.html) more bytes is reserved
+/// before encoding, so the vector is reallocated at most once.
+///
+/// Returns the number of appended bytes.
+///
+/// # Examples
+///
+/// ```
+/// let mut output = b"data: ".to_vec();
+/// let appended = ecoji::encode_append(b"input data", &mut output);
+///
+/// assert_eq!(output, "data: 👶😲🇲👅🍉🔙🌥🌩".as_bytes());
+/// assert_eq!(appended, output.len() - 6);
+/// ```
+pub fn encode_append(input: &[u8], out: &mut Vec<u8>) -> usize {
+    out.reserve(max_encoded_len(input.len()));
+    encode_up_to(&Alphabet::STANDARD, &mut &input[..], out, None).expect("Writing to a vector cannot fail")
+}
+
+/// Encodes the rest of the cursor's data into the Ecoji format, from its current position to
+/// the end, and returns a new cursor over the UTF-8 representation of the encoded data.
+///
+/// The source cursor is advanced to the end of its data. The returned cursor is positioned at
+/// the end of the encoded data, so further writes to it append to the output, e.g. when
+/// several pieces are encoded one after another; rewind it with `set_position(0)` to read the
+/// output, e.g. to pass it to [`decode_cursor`](fn.decode_cursor.html). Since the length of the
+/// data is known, the output is allocated only once, like with
+/// [`encode_slice_to_string`](fn.encode_slice_to_string.html).
+///
+/// # Examples
+///
+/// Round trip of in-memory data:
+///
+/// ```
+/// use std::io::Cursor;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let mut input = Cursor::new(b"input data".to_vec());
+///
+/// let mut encoded = ecoji::encode_cursor(&mut input);
+/// assert_eq!(encoded.get_ref().as_slice(), "👶😲🇲👅🍉🔙🌥🌩".as_bytes());
+/// assert_eq!(input.position(), 10);
+///
+/// encoded.set_position(0);
+/// let decoded = ecoji::decode_cursor(&mut encoded)?;
+/// assert_eq!(decoded.into_inner(), b"input data");
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn encode_cursor<T: AsRef<[u8]>>(source: &mut Cursor<T>) -> Cursor<Vec<u8>> {
+    let (len, output) = {
+        let data = source.get_ref().as_ref();
+        let start = cmp::min(source.position(), data.len() as u64) as usize;
+        let mut output = Vec::with_capacity(max_encoded_len(data.len() - start));
+        encode_append(&data[start..], &mut output);
+        (data.len() as u64, output)
+    };
+    source.set_position(len);
+
+    let mut output = Cursor::new(output);
+    output.set_position(output.get_ref().len() as u64);
+    output
+}
+
+thread_local! {
+    static ENCODE_BUFFER: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Encodes the given bytes into the Ecoji format using a reusable thread-local buffer, and
+/// returns the result as a new owned string.
+///
+/// The output is the same as the output of [`encode_to_string`](fn.encode_to_string.html). The
+/// difference is that the encoding happens in a buffer which is kept between calls on the same
+/// thread, so the only allocation is the final copy of the exactly-sized result, instead of the
+/// repeated reallocations of a growing buffer. This is useful in tight loops encoding many
+/// payloads of similar sizes.
+///
+/// The buffer keeps the capacity needed for the largest output encoded on the thread until the
+/// thread exits. No user code runs while the buffer is in use, so calls cannot overlap; if the
+/// buffer is unavailable nonetheless, e.g. when called from a thread-local destructor, the data
+/// is encoded into a fresh buffer instead.
+///
+/// # Examples
+///
+/// ```
+/// let output = ecoji::encode_to_string_cached(b"input data");
+///
+/// assert_eq!(output, "👶😲🇲👅🍉🔙🌥🌩");
+/// ```
+pub fn encode_to_string_cached(input: &[u8]) -> String {
+    let encode_into = |buffer: &mut Vec<u8>| {
+        buffer.clear();
+        encode_vectored(&[input], buffer).expect("Writing to a vector cannot fail");
+        // encoded output is guaranteed to be valid UTF-8
+        unsafe { String::from_utf8_unchecked(buffer.as_slice().to_vec()) }
+    };
+
+    ENCODE_BUFFER
+        .try_with(|buffer| buffer.try_borrow_mut().ok().map(|mut buffer| encode_into(&mut buffer)))
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| encode_into(&mut Vec::new()))
+}
+
+/// A writer which encodes all data written to it into the Ecoji format and writes a UTF-8
+/// representation of the encoded data to the wrapped writer.
+///
+/// Data is encoded in groups of five bytes, so up to five written bytes are kept in an internal
+/// buffer until their group is complete. Because an incomplete group can only be the last one
+/// in an Ecoji stream, flushing the encoder writes out complete groups only. The final
+/// group is written when the encoder is finished with [`finish`](#method.finish) or dropped;
+/// errors which happen when the encoder is dropped are ignored, so it is recommended to call
+/// `finish` explicitly.
+///
+/// The output is exactly the same as the output of [`encode`](fn.encode.html) for the
+/// concatenation of all written data.
+///
+/// The encoder does not implement `Clone`, even if the wrapped writer does: a clone would carry
+/// the same buffered bytes, so the final group would be written twice, once by each encoder.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Write;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let mut encoder = ecoji::Encoder::new(Vec::new());
+/// encoder.write_all(b"input")?;
+/// encoder.write_all(b" data")?;
+/// let output = encoder.finish()?;
+///
+/// assert_eq!(output, "👶😲🇲👅🍉🔙🌥🌩".as_bytes());
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub struct Encoder<W: Write> {
+    inner: Option<W>,
+    state: EncodeState,
+}
+
+impl<W: Write> Encoder<W> {
+    /// Creates a new encoder writing the encoded data to the given writer.
+    pub fn new(inner: W) -> Encoder<W> {
+        Encoder::with_alphabet(&Alphabet::STANDARD, inner)
+    }
+
+    pub(crate) fn with_alphabet(alphabet: &Alphabet, inner: W) -> Encoder<W> {
+        Encoder {
+            inner: Some(inner),
+            state: EncodeState::new(alphabet),
+        }
+    }
+
+    /// Returns a reference to the wrapped writer.
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    /// Returns a mutable reference to the wrapped writer.
+    ///
+    /// Writing to the wrapped writer directly is likely to corrupt the encoded stream.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().unwrap()
+    }
+
+    /// Writes the final, possibly incomplete, group of the encoded data to the wrapped writer
+    /// and returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let result = self.write_group();
+        let inner = self.inner.take().unwrap();
+        result.map(|_| inner)
+    }
+
+    fn write_group(&mut self) -> io::Result<()> {
+        // The group is only removed once it is written, so that a failed write can be retried
+        if let Some(group) = self.state.group() {
+            write_group(&group, self.inner.as_mut().unwrap())?;
+            self.state.clear();
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let mut consumed = 0;
+        while consumed < data.len() {
+            if self.state.is_full() {
+                if let Err(e) = self.write_group() {
+                    return if consumed == 0 { Err(e) } else { Ok(consumed) };
+                }
+            }
+
+            consumed += self.state.feed(&data[consumed..]);
+        }
+        Ok(consumed)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.state.is_full() {
+            self.write_group()?;
+        }
+        self.get_mut().flush()
+    }
+}
+
+impl<W: Write> Drop for Encoder<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.write_group();
+        }
+    }
+}
+
+impl<W: Write + fmt::Debug> fmt::Debug for Encoder<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Encoder")
+            .field("inner", &self.inner)
+            .field("alphabet", &self.state.alphabet)
+            .field("buffered", &self.state.buffered())
+            .finish()
+    }
+}
+
+/// A reader which encodes data read from the wrapped reader into the Ecoji format and yields
+/// a UTF-8 representation of the encoded data.
+///
+/// This is the counterpart of [`Decoder`](struct.Decoder.html): the encoded data can be passed to
+/// any consumer of `Read`, e.g. copied into a socket with `io::copy`, without building it in
+/// memory first. Data is encoded group by group, as it is read from the reader, so reads may
+/// return fewer bytes than requested; the UTF-8 representation of a group is kept in an internal
+/// buffer until it is read completely, so buffers of any size, even a single byte, can be used.
+///
+/// The output is exactly the same as the output of [`encode`](fn.encode.html) for the data of
+/// the wrapped reader.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Read;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let mut output = String::new();
+/// ecoji::EncodingReader::new("input data".as_bytes()).read_to_string(&mut output)?;
+///
+/// assert_eq!(output, "👶😲🇲👅🍉🔙🌥🌩");
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+#[derive(Clone)]
+pub struct EncodingReader<R> {
+    inner: R,
+    state: EncodeState,
+    buf: [u8; CHARS_PER_GROUP * MAX_EMOJI_UTF8_LEN],
+    len: usize,
+    pos: usize,
+    done: bool,
+}
+
+impl<R: Read> EncodingReader<R> {
+    /// Creates a new encoding reader reading the data to encode from the given reader.
+    pub fn new(inner: R) -> EncodingReader<R> {
+        EncodingReader::with_alphabet(&Alphabet::STANDARD, inner)
+    }
+
+    pub(crate) fn with_alphabet(alphabet: &Alphabet, inner: R) -> EncodingReader<R> {
+        EncodingReader {
+            inner,
+            state: EncodeState::new(alphabet),
+            buf: [0; CHARS_PER_GROUP * MAX_EMOJI_UTF8_LEN],
+            len: 0,
+            pos: 0,
+            done: false,
+        }
+    }
+
+    /// Returns a reference to the wrapped reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped reader.
+    ///
+    /// Reading from the wrapped reader directly may split a group of the input, which will
+    /// likely produce padding in the middle of the encoded data.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Returns the wrapped reader; encoded data which has not been read yet is discarded.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for EncodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let n = {
+            let available = self.fill_buf()?;
+            let n = cmp::min(buf.len(), available.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+/// The buffer of an encoding reader holds the UTF-8 representation of the current group, so
+/// `fill_buf` returns at most four code points at a time.
+impl<R: Read> BufRead for EncodingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.len && !self.done {
+            // Only the last group may be incomplete, so the end of the source ends the input
+            let group = if read_group(&mut self.state, &mut self.inner)? {
+                self.state.take_group()
+            } else {
+                self.done = true;
+                self.state.finish()
+            };
+            self.len = match group {
+                Some(group) => group_into_utf8(&group, &mut self.buf),
+                None => 0,
+            };
+            self.pos = 0;
+        }
+
+        Ok(&self.buf[self.pos..self.len])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = cmp::min(self.pos + amt, self.len);
+    }
+}
+
+impl<R: fmt::Debug> fmt::Debug for EncodingReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EncodingReader")
+            .field("inner", &self.inner)
+            .field("alphabet", &self.state.alphabet)
+            .field("buffered", &(self.len - self.pos))
+            .finish()
+    }
+}
+
+/// An encoder driven by the caller: input bytes are pushed into it, and encoded code points are
+/// pulled out of it one at a time.
+///
+/// This is the inverse of [`Encoder`](struct.Encoder.html), which encodes data as it is written.
+/// A pull encoder never blocks and never performs any I/O, which makes it suitable for event loops
+/// and cooperative scheduling, where each step should do a small bounded amount of work.
+///
+/// Code points become available as soon as a group of five bytes is complete. Because only the
+/// last group may be incomplete, the remaining bytes are encoded only after the end of the input
+/// is signalled with [`end`](#method.end).
+///
+/// The output is exactly the same as the output of [`encode`](fn.encode.html) for the
+/// concatenation of all pushed data.
+///
+/// # Examples
+///
+/// ```
+/// let mut encoder = ecoji::PullEncoder::new();
+/// let mut output = String::new();
+///
+/// encoder.push(b"input da");
+/// while let Some(c) = encoder.next_char() {
+///     output.push(c);
+/// }
+/// assert_eq!(output, "👶😲🇲👅");
+///
+/// encoder.push(b"ta");
+/// encoder.end();
+/// while let Some(c) = encoder.next_char() {
+///     output.push(c);
+/// }
+/// assert_eq!(output, "👶😲🇲👅🍉🔙🌥🌩");
+/// assert!(encoder.is_finished());
+/// ```
+#[derive(Debug, Clone)]
+pub struct PullEncoder {
+    state: EncodeState,
+    input: VecDeque<u8>,
+    ended: bool,
+    group: [char; CHARS_PER_GROUP],
+    pos: usize,
+}
+
+impl Default for PullEncoder {
+    fn default() -> PullEncoder {
+        PullEncoder::new()
+    }
+}
+
+impl PullEncoder {
+    /// Creates a new pull encoder without any input.
+    pub fn new() -> PullEncoder {
+        PullEncoder::with_alphabet(&Alphabet::STANDARD)
+    }
+
+    pub(crate) fn with_alphabet(alphabet: &Alphabet) -> PullEncoder {
+        PullEncoder {
+            state: EncodeState::new(alphabet),
+            input: VecDeque::new(),
+            ended: false,
+            group: ['\0'; CHARS_PER_GROUP],
+            pos: CHARS_PER_GROUP,
+        }
+    }
+
+    /// Appends bytes to the input of the encoder.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the end of the input has already been signalled with [`end`](#method.end).
+    pub fn push(&mut self, bytes: &[u8]) {
+        assert!(!self.ended, "Cannot push input after its end");
+        self.input.extend(bytes);
+    }
+
+    /// Signals that no more input will be pushed, so that the last incomplete group can be
+    /// encoded.
+    pub fn end(&mut self) {
+        self.ended = true;
+    }
+
+    /// Returns the next encoded code point, or `None` if there is not enough input to produce it.
+    ///
+    /// `None` is returned until more input is pushed or the end of the input is signalled;
+    /// use [`is_finished`](#method.is_finished) to find out whether all output has been produced.
+    pub fn next_char(&mut self) -> Option<char> {
+        if self.pos == self.group.len() {
+            while !self.state.is_full() && !self.input.is_empty() {
+                let n = self.state.feed(self.input.as_slices().0);
+                self.input.drain(..n);
+            }
+            self.group = if self.state.is_full() {
+                self.state.take_group()?
+            } else if self.ended {
+                self.state.finish()?
+            } else {
+                return None;
+            };
+            self.pos = 0;
+        }
+
+        let c = self.group[self.pos];
+        self.pos += 1;
+        Some(c)
+    }
+
+    /// Checks whether the end of the input has been signalled and all of it has been encoded and
+    /// pulled out.
+    pub fn is_finished(&self) -> bool {
+        self.ended && self.input.is_empty() && self.state.buffered() == 0 && self.pos == self.group.len()
+    }
+}
+
+/// The kind of a code point in an encoded group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PaddingKind {
+    /// A code point of the alphabet carrying 10 bits of data.
+    Data,
+    /// The padding code point filling up the group (`PADDING`).
+    Padding,
+    /// One of the four padding code points which end a 4-byte group and carry the two low bits
+    /// of its last byte (`PADDING_40` to `PADDING_43`).
+    Padding4x,
+}
+
+/// Returns the layout of the last group of a well-formed encoding of an input of the given length.
+///
+/// Only the number of bytes in the last group matters. When the input length is a multiple of
+/// five, the last group is complete and consists of data code points only; this is also what is
+/// returned for an empty input, which has no groups at all.
+///
+/// # Examples
+///
+/// ```
+/// use ecoji::PaddingKind::*;
+///
+/// assert_eq!(ecoji::expected_padding(3), [Data, Data, Data, Padding]);
+/// assert_eq!(ecoji::expected_padding(9), [Data, Data, Data, Padding4x]);
+/// assert_eq!(ecoji::expected_padding(10), [Data, Data, Data, Data]);
+/// ```
+pub fn expected_padding(input_len: usize) -> [PaddingKind; CHARS_PER_GROUP] {
+    use self::PaddingKind::*;
+
+    match input_len % BYTES_PER_GROUP {
+        0 => [Data, Data, Data, Data],
+        1 => [Data, Padding, Padding, Padding],
+        2 => [Data, Data, Padding, Padding],
+        3 => [Data, Data, Data, Padding],
+        _ => [Data, Data, Data, Padding4x],
+    }
+}
+
+/// Returns the number of code points in the encoding of an input of the given length.
+///
+/// Every group of up to five input bytes is encoded as four code points.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(ecoji::encoded_chars(0), 0);
+/// assert_eq!(ecoji::encoded_chars(3), 4);
+/// assert_eq!(ecoji::encoded_chars(10), 8);
+/// assert_eq!(ecoji::encoded_chars(11), 12);
+/// ```
+pub fn encoded_chars(input_len: usize) -> usize {
+    input_len.div_ceil(BYTES_PER_GROUP) * CHARS_PER_GROUP
+}
+
+/// Returns the maximum number of bytes in the UTF-8 representation of the encoding of an input
+/// of the given length.
+///
+/// This is the number of code points given by [`encoded_chars`](fn.encoded_chars.html) times
+/// the longest UTF-8 representation of a code point of the alphabet, so it is an upper bound
+/// suitable for preallocating output buffers.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(ecoji::max_encoded_len(0), 0);
+/// assert_eq!(ecoji::max_encoded_len(10), 32);
+/// assert!(ecoji::encode_slice_to_string(b"input data").len() <= ecoji::max_encoded_len(10));
+/// ```
+pub fn max_encoded_len(input_len: usize) -> usize {
+    encoded_chars(input_len) * MAX_EMOJI_UTF8_LEN
+}
+
+/// Returns the largest number of input bytes whose encoding fits into the given number of code
+/// points.
+///
+/// This is the inverse of [`encoded_chars`](fn.encoded_chars.html): every group of four code
+/// points holds up to five bytes. Encoded data always consists of whole groups, so if `chars` is
+/// not a multiple of 4, it is rounded down.
+///
+/// # Examples
+///
+/// ```
+/// // How much data fits into a message limited to 280 visible characters?
+/// assert_eq!(ecoji::max_input_bytes(280), 350);
+/// assert_eq!(ecoji::max_input_bytes(7), 5);
+/// assert_eq!(ecoji::max_input_bytes(3), 0);
+/// ```
+pub fn max_input_bytes(chars: usize) -> usize {
+    chars / CHARS_PER_GROUP * BYTES_PER_GROUP
+}
+
+/// Encodes the input and returns each output code point together with its index in the Ecoji
+/// alphabet.
+///
+/// This function is meant as an aid for testing other implementations of Ecoji: comparing index
+/// sequences makes it possible to check that input bytes are grouped correctly, independently of
+/// the alphabet rendering.
+///
+/// The input is split into groups of five bytes (40 bits), and each group is turned into four
+/// 10-bit indices, most significant bits first: the first index consists of the 8 bits of the
+/// first byte followed by the 2 high bits of the second byte, and so on. Padding characters
+/// are reported with the indices following the alphabet: 1024 for `PADDING` and 1025 to 1028
+/// for the four padding characters which may end a 4-byte group.
+///
+/// # Examples
+///
+/// ```
+/// let trace = ecoji::debug_encode_trace(&[0xAB, 0xCD, 0xEF, 0x01, 0x23, 0x00]);
+/// let indices: Vec<_> = trace.iter().map(|&(i, _)| i).collect();
+///
+/// assert_eq!(indices, [687, 222, 960, 291, 0, 1024, 1024, 1024]);
+/// ```
+pub fn debug_encode_trace(input: &[u8]) -> Vec<(usize, char)> {
+    input.chunks(BYTES_PER_GROUP)
+        .flat_map(|chunk| encode_group(&Alphabet::STANDARD, chunk).to_vec())
+        .map(|c| (Alphabet::STANDARD.index_of(c).expect("Encoded character is not in the alphabet"), c))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emojis::*;
+
+    fn check(input: &[u8], output: &[u8]) {
+        let buf = encode_to_string(&mut &input[..]).unwrap();
+        assert_eq!(output, buf.as_bytes());
+    }
+
+    fn check_chars(input: &[u8], output: &[char]) {
+        let buf = encode_to_string(&mut &input[..]).unwrap();
+        let chars: Vec<_> = buf.chars().collect();
+        assert_eq!(output, chars.as_slice());
+    }
+
+    #[test]
+    fn test_group_boundary_has_no_padding() {
+        let is_padding = |c: &char| [PADDING, PADDING_40, PADDING_41, PADDING_42, PADDING_43].contains(c);
+        let data: Vec<u8> = (0..15).map(|i| i * 17).collect();
+
+        for &len in &[5, 10, 15] {
+            let encoded = encode_to_string(&mut &data[..len]).unwrap();
+            let chars: Vec<_> = encoded.chars().collect();
+            assert_eq!(chars.len(), len / 5 * 4);
+            assert!(!chars.iter().any(is_padding));
+        }
+    }
+
+    #[test]
+    fn test_expected_padding() {
+        let data: Vec<u8> = (0..12).map(|i| 0xff - i * 13).collect();
+
+        for len in 1..data.len() + 1 {
+            let trace = debug_encode_trace(&data[..len]);
+            let kinds: Vec<_> = trace[trace.len() - 4..].iter()
+                .map(|&(i, _)| match i {
+                    0..=1023 => PaddingKind::Data,
+                    1024 => PaddingKind::Padding,
+                    _ => PaddingKind::Padding4x,
+                })
+                .collect();
+            assert_eq!(kinds, expected_padding(len), "input length {}", len);
+        }
+    }
+
+    #[test]
+    fn test_and_flush() {
+        let mut output = io::BufWriter::new(Vec::new());
+        assert_eq!(encode_and_flush(&mut &b"abc"[..], &mut output).unwrap(), 15);
+        assert_eq!(output.get_ref(), "👖📸🎈☕".as_bytes());
+
+        struct FailingFlush;
+
+        impl Write for FailingFlush {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> { Ok(buf.len()) }
+            fn flush(&mut self) -> io::Result<()> { Err(io::Error::other("flush failed")) }
+        }
+
+        let error = encode_and_flush(&mut &b"abc"[..], &mut FailingFlush).unwrap_err();
+        assert_eq!(error.to_string(), "Failed to write to the destination: flush failed");
+    }
+
+    #[test]
+    fn test_to_string_cached() {
+        let data: Vec<u8> = (0..=255).collect();
+
+        let threads: Vec<_> = (0..4).map(|t| {
+            let data = data.clone();
+            ::std::thread::spawn(move || {
+                for len in (t..data.len()).rev().step_by(7).chain(0..10) {
+                    let expected = encode_to_string(&mut &data[..len]).unwrap();
+                    assert_eq!(encode_to_string_cached(&data[..len]), expected);
+                }
+            })
+        }).collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_pull_encoder() {
+        let data: Vec<u8> = (0..37).collect();
+        for len in 0..data.len() {
+            let expected = encode_to_string(&mut &data[..len]).unwrap();
+            for piece in 1..7 {
+                let mut encoder = PullEncoder::new();
+                let mut output = String::new();
+                for chunk in data[..len].chunks(piece) {
+                    encoder.push(chunk);
+                    while let Some(c) = encoder.next_char() {
+                        output.push(c);
+                    }
+                    assert_eq!(output.chars().count() % 4, 0);
+                }
+                assert!(!encoder.is_finished());
+                encoder.end();
+                while let Some(c) = encoder.next_char() {
+                    output.push(c);
+                }
+                assert!(encoder.is_finished());
+                assert_eq!(output, expected);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot push input after its end")]
+    fn test_pull_encoder_push_after_end() {
+        let mut encoder = PullEncoder::new();
+        encoder.end();
+        encoder.push(b"a");
+    }
+
+    #[test]
+    fn test_pull_encoder_unfinished_after_end() {
+        let mut encoder = PullEncoder::new();
+        encoder.push(b"abc");
+        assert_eq!(encoder.next_char(), None);
+
+        encoder.end();
+        assert!(!encoder.is_finished());
+        let output: String = ::std::iter::from_fn(|| encoder.next_char()).collect();
+        assert_eq!(output, "👖📸🎈☕");
+        assert!(encoder.is_finished());
+    }
+
+    #[test]
+    fn test_encoding_reader() {
+        let data: Vec<u8> = (0..37).collect();
+        for len in 0..data.len() {
+            let expected = encode_to_string(&mut &data[..len]).unwrap();
+            for &buf_len in &[1, 2, 3, 7, 64] {
+                // Chaining splits reads from the source in the middle of a group
+                let source = data[..len / 2].chain(&data[len / 2..len]);
+                let mut reader = EncodingReader::new(source);
+                let mut output = Vec::new();
+                let mut buf = vec![0; buf_len];
+                loop {
+                    match reader.read(&mut buf).unwrap() {
+                        0 => break,
+                        n => output.extend_from_slice(&buf[..n]),
+                    }
+                }
+                assert_eq!(String::from_utf8(output).unwrap(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_encoding_reader_source_error() {
+        struct Failing;
+
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::BrokenPipe, "failure"))
+            }
+        }
+
+        let mut output = Vec::new();
+        let error = io::copy(&mut EncodingReader::new(b"abcde".chain(Failing)), &mut output).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+        assert_eq!(error.get_ref().and_then(|e| e.downcast_ref::<EncodeError>()).map(|e| e.to_string()),
+                   Some("Failed to read from the source: failure".to_owned()));
+    }
+
+    #[test]
+    fn test_encoding_reader_retry_after_error() {
+        /// A reader which fails with `WouldBlock` once after every three bytes.
+        struct Blocking<'a> {
+            data: &'a [u8],
+            since_block: usize,
+        }
+
+        impl<'a> Read for Blocking<'a> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.since_block == 3 {
+                    self.since_block = 0;
+                    return Err(io::Error::new(io::ErrorKind::WouldBlock, "not ready"));
+                }
+                let n = cmp::min(buf.len(), 3 - self.since_block);
+                let n = self.data.read(&mut buf[..n])?;
+                self.since_block += n;
+                Ok(n)
+            }
+        }
+
+        let data = b"0123456789abcde";
+        let mut reader = EncodingReader::new(Blocking { data, since_block: 0 });
+        let mut output = Vec::new();
+        let mut buf = [0; 7];
+        let mut blocked = 0;
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => output.extend_from_slice(&buf[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => blocked += 1,
+                Err(e) => panic!("unexpected error: {}", e),
+            }
+        }
+
+        assert!(blocked > 0);
+        assert_eq!(String::from_utf8(output).unwrap(), encode_slice_to_string(data));
+    }
+
+    #[test]
+    fn test_slice_to_string() {
+        let data: Vec<u8> = (0..64).map(|i| i * 3 + 1).collect();
+        for len in 0..data.len() {
+            let output = encode_slice_to_string(&data[..len]);
+            assert_eq!(output, encode_to_string(&mut &data[..len]).unwrap());
+            assert!(output.capacity() >= max_encoded_len(len));
+
+            // The capacity allocated up front is enough, so encoding never reallocates the buffer
+            let mut buf = Vec::with_capacity(max_encoded_len(len));
+            let ptr = buf.as_ptr();
+            encode_append(&data[..len], &mut buf);
+            assert_eq!(buf.as_ptr(), ptr);
+            assert_eq!(buf, output.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_append() {
+        let data: Vec<u8> = (0..23).map(|i| i * 9).collect();
+        let mut output = Vec::new();
+        let mut expected = String::new();
+        for len in 0..data.len() {
+            let previous_len = output.len();
+            let appended = encode_append(&data[..len], &mut output);
+
+            let encoded = encode_to_string(&mut &data[..len]).unwrap();
+            assert_eq!(appended, encoded.len());
+            expected += &encoded;
+            assert_eq!(output, expected.as_bytes());
+            assert!(output.capacity() >= previous_len + max_encoded_len(len));
+        }
+    }
+
+    #[test]
+    fn test_max_input_bytes() {
+        for chars in 0..64 {
+            let max = max_input_bytes(chars);
+            assert!(encoded_chars(max) <= chars);
+            assert!(encoded_chars(max + 1) > chars);
+
+            let data = vec![0xa5; max];
+            assert_eq!(encode_to_string(&mut data.as_slice()).unwrap().chars().count(), encoded_chars(max));
+        }
+    }
+
+    #[test]
+    fn test_chars_to() {
+        let data: Vec<u8> = (0..64).map(|i| i * 3).collect();
+        for len in 0..data.len() {
+            let mut output = String::new();
+            encode_chars_to(&data[..len], |c| output.push(c));
+            assert_eq!(output, encode_to_string(&mut &data[..len]).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_limited() {
+        let mut output = Vec::new();
+        assert_eq!(encode_limited(&mut &b"abc"[..], &mut output, 3).unwrap(), output.len());
+        assert_eq!(output, "👖📸🎈☕".as_bytes());
+
+        // only the complete groups before the one crossing the limit are written
+        let cases: &[(&[u8], usize, &[u8])] = &[
+            (b"abcdefg", 6, b"abcde"),
+            (b"abcdefgh", 7, b"abcde"),
+            (b"abcdefghijk", 10, b"abcdefghij"),
+            (b"abcd", 3, b""),
+        ];
+        for &(input, limit, written) in cases {
+            let mut output = Vec::new();
+            let error = encode_limited(&mut &input[..], &mut output, limit).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(
+                error.get_ref().and_then(|e| e.downcast_ref::<EncodeError>()),
+                Some(&EncodeError::InputTooLarge { limit })
+            );
+            assert_eq!(output, encode_to_string(&mut &written[..]).unwrap().as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_repeated_chunks() {
+        let mut input = vec![0; 53];
+        input.extend(vec![0xff; 20]);
+        input.extend_from_slice(b"abcdeabcdeabcdxabcde");
+        input.extend(vec![0; 7]);
+
+        for len in 0..input.len() {
+            let mut expected = String::new();
+            encode_chars_to(&input[..len], |c| expected.push(c));
+            assert_eq!(encode_to_string(&mut &input[..len]).unwrap(), expected);
+        }
+    }
+
+    /// A writer which accepts at most three bytes per `write` call, so that a single group is
+    /// never written at once.
+    struct ShortWriter(Vec<u8>);
+
+    impl Write for ShortWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = cmp::min(buf.len(), 3);
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_encode_wrapped() {
+        let data: Vec<u8> = (0..37u32).map(|i| (i * 83 + 17) as u8).collect();
+        for len in 0..data.len() {
+            let unwrapped: Vec<char> = encode_slice_to_string(&data[..len]).chars().collect();
+            assert_eq!(encode_wrapped(&data[..len], 0), unwrapped.iter().collect::<String>());
+
+            for width in 1..10 {
+                let (output, offsets) = encode_wrapped_with_offsets(&data[..len], width);
+                assert_eq!(output, encode_wrapped(&data[..len], width));
+                assert_eq!(offsets.len(), line_breaks(len, width));
+
+                let lines: Vec<&str> = output.split('\n').collect();
+                assert!(lines.iter().all(|line| line.chars().count() <= width));
+                assert!(lines[..lines.len() - 1].iter().all(|line| line.chars().count() == width));
+                assert_eq!(output.chars().filter(|&c| c != '\n').collect::<Vec<_>>(), unwrapped);
+
+                let newlines: Vec<usize> = output.chars().enumerate().filter(|&(_, c)| c == '\n').map(|(i, _)| i).collect();
+                assert_eq!(offsets, newlines);
+                assert_eq!(::decode_str(&output).unwrap(), &data[..len]);
+
+                let mut streamed = Vec::new();
+                let mut streamed_offsets = Vec::new();
+                let written = encode_wrapped_stream(&mut &data[..len], &mut streamed, width,
+                                                    &mut |i| streamed_offsets.push(i)).unwrap();
+                assert_eq!(streamed, output.as_bytes());
+                assert_eq!(written, streamed.len());
+                assert_eq!(streamed_offsets, offsets);
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_channel() {
+        use std::sync::mpsc;
+        use std::thread;
+
+        let data: Vec<u8> = (0..101u32).map(|i| (i * 71 + 3) as u8).collect();
+        for &chunk_size in &[1, 2, 4, 5, 7, 13, 101] {
+            let (tx, rx) = mpsc::channel();
+            let chunks: Vec<Vec<u8>> = data.chunks(chunk_size).map(|c| c.to_vec()).collect();
+            let producer = thread::spawn(move || {
+                for chunk in chunks {
+                    tx.send(Vec::new()).unwrap();
+                    tx.send(chunk).unwrap();
+                }
+            });
+
+            let mut output = Vec::new();
+            let n = encode_channel(rx, &mut output).unwrap();
+            producer.join().unwrap();
+            assert_eq!(n, output.len());
+            assert_eq!(output, encode_slice_to_string(&data).as_bytes());
+        }
+
+        let (tx, rx) = mpsc::channel::<Vec<u8>>();
+        drop(tx);
+        assert_eq!(encode_channel(rx, &mut Vec::new()).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_encode_cursor() {
+        let data: Vec<u8> = (0..23u32).map(|i| (i * 53 + 9) as u8).collect();
+        for start in 0..=data.len() + 1 {
+            let mut source = Cursor::new(&data[..]);
+            source.set_position(start as u64);
+            let mut output = encode_cursor(&mut source);
+
+            let expected = encode_slice_to_string(data.get(start..).unwrap_or(&[]));
+            assert_eq!(output.get_ref().as_slice(), expected.as_bytes());
+            assert_eq!(output.position(), expected.len() as u64);
+            assert_eq!(source.position(), data.len() as u64);
+
+            output.write_all(b"!").unwrap();
+            assert_eq!(output.get_ref().last(), Some(&b'!'));
+        }
+    }
+
+    #[test]
+    fn test_encode_tee() {
+        let input: Vec<u8> = (0..42u32).map(|i| (i * 19 + 5) as u8).collect();
+        let expected = encode_slice_to_string(&input);
+
+        let mut first = Vec::new();
+        let mut second = ShortWriter(Vec::new());
+        let n = encode_tee(&mut input.as_slice(), &mut [&mut first, &mut second]).unwrap();
+        assert_eq!(n, expected.len());
+        assert_eq!(first, expected.as_bytes());
+        assert_eq!(second.0, expected.as_bytes());
+
+        assert_eq!(encode_tee(&mut input.as_slice(), &mut []).unwrap(), expected.len());
+
+        // A failing destination stops encoding; the preceding ones get the failed group
+        let mut first = Vec::new();
+        let mut small = [0u8; 20];
+        let mut last = Vec::new();
+        let error = encode_tee(&mut input.as_slice(), &mut [&mut first, &mut &mut small[..], &mut last]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WriteZero);
+        assert!(matches!(
+            error.get_ref().and_then(|e| e.downcast_ref::<EncodeError>()),
+            Some(&EncodeError::DestIo(_))
+        ));
+        assert_eq!(first.len(), last.len() + 16);
+        assert!(expected.as_bytes().starts_with(&first));
+    }
+
+    #[test]
+    fn test_short_writes() {
+        let mut input = vec![0; 23];
+        input.extend((0..31).map(|i| i * 7));
+
+        for len in 0..input.len() {
+            let expected = encode_to_string(&mut &input[..len]).unwrap();
+
+            let mut output = ShortWriter(Vec::new());
+            assert_eq!(encode(&mut &input[..len], &mut output).unwrap(), expected.len());
+            assert_eq!(output.0, expected.as_bytes());
+
+            let mut output = ShortWriter(Vec::new());
+            let (first, second) = input[..len].split_at(len / 2);
+            assert_eq!(encode_vectored(&[first, second], &mut output).unwrap(), expected.len());
+            assert_eq!(output.0, expected.as_bytes());
+
+            let mut encoder = Encoder::new(ShortWriter(Vec::new()));
+            encoder.write_all(&input[..len]).unwrap();
+            assert_eq!(encoder.finish().unwrap().0, expected.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_with_separators() {
+        let data: Vec<u8> = (0..37).collect();
+        for len in 0..data.len() {
+            let plain = encode_to_string(&mut &data[..len]).unwrap();
+            for every in 1..4 {
+                let output = encode_with_separators(&data[..len], every, '·');
+                assert_eq!(output.replace('·', ""), plain);
+                assert!(!output.ends_with('·'));
+                assert_eq!(output.matches('·').count(), encoded_chars(len).saturating_sub(1) / (4 * every));
+
+                let decoded = ::DecodeOptions::new().ignore_chars(&['·']).decode_to_vec(&mut output.as_bytes()).unwrap();
+                assert_eq!(decoded, &data[..len]);
+            }
+
+            let wrapped = encode_with_separators(&data[..len], 2, '\n');
+            assert_eq!(::decode_str(&wrapped).unwrap(), &data[..len]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "is a code point of the alphabet")]
+    fn test_with_separators_in_alphabet() {
+        encode_with_separators(b"abc", 1, Alphabet::STANDARD.padding());
+    }
+
+    #[test]
+    fn test_to_indices() {
+        let input: Vec<u8> = (0..24).map(|i| i * 11).collect();
+        for len in 0..input.len() {
+            let indices = encode_to_indices(&input[..len]);
+            let encoded = encode_to_string(&mut &input[..len]).unwrap();
+            let expected: Vec<u16> = encoded.chars()
+                .map(|c| Alphabet::STANDARD.index_of(c).unwrap() as u16)
+                .collect();
+            assert_eq!(indices, expected);
+        }
+        assert_eq!(encode_to_indices(b"abcd")[3], 1025 + (b'd' & 0x03) as u16);
+    }
+
+    #[test]
+    fn test_cancellable() {
+        let input: Vec<u8> = (0..23).collect();
+        let encoded = encode_to_string(&mut input.as_slice()).unwrap();
+
+        for groups in 0..7 {
+            let mut calls = 0;
+            let mut output = Vec::new();
+            let n = encode_cancellable(&mut input.as_slice(), &mut output, &mut || {
+                calls += 1;
+                calls <= groups
+            }).unwrap();
+
+            assert_eq!(n, output.len());
+            let expected_chars = cmp::min(groups, 5) * 4;
+            assert_eq!(String::from_utf8(output).unwrap(), encoded.chars().take(expected_chars).collect::<String>());
+        }
+    }
+
+    #[test]
+    fn test_vectored() {
+        let data: Vec<u8> = (0..32).collect();
+        let expected = encode_to_string(&mut data.as_slice()).unwrap();
+
+        for first in 0..data.len() {
+            for second in first..data.len() {
+                let inputs = [&data[..first], &data[first..second], &[][..], &data[second..]];
+                let mut output = Vec::new();
+                let n = encode_vectored(&inputs, &mut output).unwrap();
+                assert_eq!(n, output.len());
+                assert_eq!(output, expected.as_bytes());
+            }
+        }
+
+        let mut output = Vec::new();
+        assert_eq!(encode_vectored(&[], &mut output).unwrap(), 0);
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_encoder() {
+        let data: Vec<u8> = (0..64).collect();
+        for len in 0..data.len() {
+            for chunk_size in 1..8 {
+                let mut encoder = Encoder::new(Vec::new());
+                for chunk in data[..len].chunks(chunk_size) {
+                    encoder.write_all(chunk).unwrap();
+                    encoder.flush().unwrap();
+                }
+                let output = encoder.finish().unwrap();
+                assert_eq!(output, encode_to_string(&mut &data[..len]).unwrap().as_bytes());
+            }
+        }
+    }
+
+    #[test]
+    fn test_encoder_finishes_on_drop() {
+        let mut output = Vec::new();
+        Encoder::new(&mut output).write_all(b"abc").unwrap();
+        assert_eq!(output, "👖📸🎈☕".as_bytes());
+    }
+
+    #[test]
+    fn test_encoder_debug() {
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.write_all(b"\x2a\x2a").unwrap();
+
+        let debug = format!("{:?}", encoder);
+        assert_eq!(debug, r#"Encoder { inner: Some([]), alphabet: Alphabet("standard"), buffered: 2 }"#);
+    }
+
+    #[test]
+    fn test_trace() {
+        let trace = debug_encode_trace(&[0, 1, 2, 3]);
+        assert_eq!(trace, vec![(0, EMOJIS[0]), (16, EMOJIS[16]), (128, EMOJIS[128]), (1028, PADDING_43)]);
+
+        let trace = debug_encode_trace(b"k");
+        assert_eq!(trace[0], (('k' as usize) << 2, EMOJIS[('k' as usize) << 2]));
+        assert_eq!(&trace[1..], &[(1024, PADDING), (1024, PADDING), (1024, PADDING)]);
+
+        assert!(debug_encode_trace(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_random() {
+        check(b"abc", "👖📸🎈☕".as_bytes());
+    }
+
+    #[test]
+    fn test_one_byte() {
+        check_chars(b"k", &[EMOJIS[('k' as usize) << 2], PADDING, PADDING, PADDING]);
+    }
+
+    #[test]
+    fn test_two_bytes() {
+        check_chars(&[0, 1], &[EMOJIS[0], EMOJIS[16], PADDING, PADDING]);
+    }
+
+    #[test]
+    fn test_three_bytes() {
+        check_chars(&[0, 1, 2], &[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING]);
+    }
+
+    #[test]
+    fn test_four_bytes() {
+        check_chars(&[0, 1, 2, 0], &[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING_40]);
+        check_chars(&[0, 1, 2, 1], &[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING_41]);
+        check_chars(&[0, 1, 2, 2], &[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING_42]);
+        check_chars(&[0, 1, 2, 3], &[EMOJIS[0], EMOJIS[16], EMOJIS[128], PADDING_43]);
+    }
+
+    #[test]
+    fn test_five_bytes() {
+        check_chars(&[0xAB, 0xCD, 0xEF, 0x01, 0x23], &[EMOJIS[687], EMOJIS[222], EMOJIS[960], EMOJIS[291]]);
+    }
+
+    /// A reader which returns at most one byte per call and is interrupted before every byte.
+    struct TrickleReader<'a> {
+        data: &'a [u8],
+        interrupt: bool,
+    }
+
+    impl<'a> Read for TrickleReader<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+            }
+            if self.data.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.data[0];
+            self.data = &self.data[1..];
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_chunks_are_in_range_for_any_input_length() {
+        let data: Vec<u8> = (0..64).collect();
+        for len in 0..data.len() {
+            let input = &data[..len];
+
+            let mut output = Vec::new();
+            let n = encode(&mut TrickleReader { data: input, interrupt: false }, &mut output).unwrap();
+            assert_eq!(n, output.len());
+            assert_eq!(String::from_utf8(output).unwrap(), encode_to_string(&mut &input[..]).unwrap());
+            assert_eq!(encode_to_string(&mut &input[..]).unwrap().chars().count(), len.div_ceil(5) * 4);
+        }
     }
 }
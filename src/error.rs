@@ -0,0 +1,277 @@
+//! Errors describing failures which are specific to the Ecoji format.
+//!
+//! All encoding and decoding functions return `std::io::Error`s, which wrap one of the errors
+//! defined here; they can be retrieved with `std::io::Error::get_ref()` and downcasting.
+//!
+//! Failures of the underlying source or destination are wrapped into the `SourceIo` and `DestIo`
+//! variants, which tell whether reading or writing failed. The wrapping `std::io::Error` has the
+//! same kind as the original error, so checking the kind works just as with unwrapped errors.
+//! Other failures are reported with the `InvalidData` kind.
+//!
+//! I/O variants compare equal when the original errors have the same kind.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+/// An encoding failure.
+#[derive(Debug, Clone)]
+pub enum EncodeError {
+    /// The source contains more than the allowed maximum of `limit` bytes.
+    InputTooLarge { limit: usize },
+    /// Reading from the source failed.
+    SourceIo(Arc<io::Error>),
+    /// Writing to the destination failed.
+    DestIo(Arc<io::Error>),
+}
+
+impl EncodeError {
+    pub(crate) fn into_io(self) -> io::Error {
+        let kind = match self {
+            EncodeError::SourceIo(ref e) | EncodeError::DestIo(ref e) => e.kind(),
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, self)
+    }
+
+    pub(crate) fn wrap_source(e: io::Error) -> io::Error {
+        EncodeError::SourceIo(Arc::new(e)).into_io()
+    }
+
+    pub(crate) fn wrap_destination(e: io::Error) -> io::Error {
+        EncodeError::DestIo(Arc::new(e)).into_io()
+    }
+}
+
+impl PartialEq for EncodeError {
+    fn eq(&self, other: &EncodeError) -> bool {
+        match (self, other) {
+            (&EncodeError::InputTooLarge { limit: a }, &EncodeError::InputTooLarge { limit: b }) => a == b,
+            (&EncodeError::SourceIo(ref a), &EncodeError::SourceIo(ref b)) |
+            (&EncodeError::DestIo(ref a), &EncodeError::DestIo(ref b)) => a.kind() == b.kind(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for EncodeError {}
+
+impl Error for EncodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            EncodeError::SourceIo(ref e) | EncodeError::DestIo(ref e) => Some(&**e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EncodeError::InputTooLarge { limit } =>
+                write!(f, "Input data exceeds the maximum allowed size of {} bytes", limit),
+            EncodeError::SourceIo(ref e) => write!(f, "Failed to read from the source: {}", e),
+            EncodeError::DestIo(ref e) => write!(f, "Failed to write to the destination: {}", e),
+        }
+    }
+}
+
+/// A decoding failure.
+#[derive(Debug, Clone)]
+pub enum DecodeError {
+    /// The decoded data would be larger than the allowed maximum of `limit` bytes.
+    OutputTooLarge { limit: usize },
+    /// The input contains a code point which is not a part of the alphabet; `position` is the
+    /// index of this code point in the input, counting from zero.
+    NotInAlphabet { c: char, position: u64 },
+    /// The input does not start with the marker expected by
+    /// [`decode_with_marker`](fn.decode_with_marker.html).
+    MissingMarker,
+    /// A group is followed by the code point `c` at `position` instead of the delimiter set with
+    /// [`DecodeOptions::delimiter`](struct.DecodeOptions.html#method.delimiter).
+    MissingDelimiter { delimiter: char, c: char, position: u64 },
+    /// The input of [`decode_from_indices`](fn.decode_from_indices.html) contains an index which
+    /// does not correspond to a code point of the alphabet at `position`.
+    InvalidIndex { index: u16, position: u64 },
+    /// The code point `found` found by [`validate`](fn.validate.html) is not a part of the
+    /// alphabet. It is the `char_in_group`-th code point of the group `group_index`, both counting
+    /// from zero, and starts at `byte_offset` in the validated string.
+    InvalidGroup { found: char, group_index: usize, char_in_group: usize, byte_offset: usize },
+    /// The data decoded by [`decode_exact`](fn.decode_exact.html) is `actual` bytes long instead of
+    /// exactly `expected` bytes.
+    LengthMismatch { expected: usize, actual: usize },
+    /// The data decoded by [`decode_trimmed`](fn.decode_trimmed.html) does not start with a valid
+    /// header, or the header describes data too large to be allocated.
+    MalformedHeader,
+    /// The data decoded by [`decode_checked`](fn.decode_checked.html) is empty, so it does not
+    /// even contain the checksum byte.
+    MissingChecksum,
+    /// The checksum byte `expected` at the end of the data decoded by
+    /// [`decode_checked`](fn.decode_checked.html) differs from the checksum `actual` of the data.
+    ChecksumMismatch { expected: u8, actual: u8 },
+    /// The padding code point `c` at `position` is in a slot of a group which must hold data, i.e.
+    /// at the start of a group, before a data code point or before one of the paddings ending
+    /// a 4-byte group.
+    MisplacedPadding { c: char, position: u64 },
+    /// The input ends with the incomplete group `group_index` starting at `byte_offset`. This is
+    /// only reported by [`validate_all`](fn.validate_all.html); other functions return an error of
+    /// the `UnexpectedEof` kind instead.
+    IncompleteGroup { group_index: usize, byte_offset: usize },
+    /// Reading from the source failed.
+    SourceIo(Arc<io::Error>),
+    /// Writing to the destination failed.
+    DestIo(Arc<io::Error>),
+}
+
+impl DecodeError {
+    pub(crate) fn into_io(self) -> io::Error {
+        let kind = match self {
+            DecodeError::SourceIo(ref e) | DecodeError::DestIo(ref e) => e.kind(),
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, self)
+    }
+
+    pub(crate) fn wrap_source(e: io::Error) -> io::Error {
+        DecodeError::SourceIo(Arc::new(e)).into_io()
+    }
+
+    pub(crate) fn wrap_destination(e: io::Error) -> io::Error {
+        DecodeError::DestIo(Arc::new(e)).into_io()
+    }
+}
+
+impl PartialEq for DecodeError {
+    fn eq(&self, other: &DecodeError) -> bool {
+        match (self, other) {
+            (&DecodeError::OutputTooLarge { limit: a }, &DecodeError::OutputTooLarge { limit: b }) => a == b,
+            (&DecodeError::NotInAlphabet { c: c1, position: p1 }, &DecodeError::NotInAlphabet { c: c2, position: p2 }) =>
+                c1 == c2 && p1 == p2,
+            (&DecodeError::MissingMarker, &DecodeError::MissingMarker) => true,
+            (&DecodeError::MissingDelimiter { delimiter: d1, c: c1, position: p1 },
+             &DecodeError::MissingDelimiter { delimiter: d2, c: c2, position: p2 }) => d1 == d2 && c1 == c2 && p1 == p2,
+            (&DecodeError::InvalidIndex { index: i1, position: p1 }, &DecodeError::InvalidIndex { index: i2, position: p2 }) =>
+                i1 == i2 && p1 == p2,
+            (&DecodeError::InvalidGroup { found: f1, group_index: g1, char_in_group: c1, byte_offset: o1 },
+             &DecodeError::InvalidGroup { found: f2, group_index: g2, char_in_group: c2, byte_offset: o2 }) =>
+                f1 == f2 && g1 == g2 && c1 == c2 && o1 == o2,
+            (&DecodeError::LengthMismatch { expected: e1, actual: a1 },
+             &DecodeError::LengthMismatch { expected: e2, actual: a2 }) => e1 == e2 && a1 == a2,
+            (&DecodeError::MalformedHeader, &DecodeError::MalformedHeader) => true,
+            (&DecodeError::MissingChecksum, &DecodeError::MissingChecksum) => true,
+            (&DecodeError::ChecksumMismatch { expected: e1, actual: a1 },
+             &DecodeError::ChecksumMismatch { expected: e2, actual: a2 }) => e1 == e2 && a1 == a2,
+            (&DecodeError::MisplacedPadding { c: c1, position: p1 }, &DecodeError::MisplacedPadding { c: c2, position: p2 }) =>
+                c1 == c2 && p1 == p2,
+            (&DecodeError::IncompleteGroup { group_index: g1, byte_offset: o1 },
+             &DecodeError::IncompleteGroup { group_index: g2, byte_offset: o2 }) => g1 == g2 && o1 == o2,
+            (&DecodeError::SourceIo(ref a), &DecodeError::SourceIo(ref b)) |
+            (&DecodeError::DestIo(ref a), &DecodeError::DestIo(ref b)) => a.kind() == b.kind(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for DecodeError {}
+
+impl Error for DecodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            DecodeError::SourceIo(ref e) | DecodeError::DestIo(ref e) => Some(&**e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecodeError::OutputTooLarge { limit } =>
+                write!(f, "Decoded data exceeds the maximum allowed size of {} bytes", limit),
+            DecodeError::NotInAlphabet { c, position } =>
+                write!(f, "invalid char {:?} (U+{:04X}) at position {}", c, c as u32, position),
+            DecodeError::MissingMarker =>
+                write!(f, "Input data does not start with the Ecoji marker"),
+            DecodeError::MissingDelimiter { delimiter, c, position } => write!(
+                f, "expected delimiter {:?} after a group, found char {:?} (U+{:04X}) at position {}",
+                delimiter, c, c as u32, position
+            ),
+            DecodeError::InvalidIndex { index, position } =>
+                write!(f, "invalid code point index {} at position {}", index, position),
+            DecodeError::InvalidGroup { found, group_index, char_in_group, byte_offset } => write!(
+                f, "invalid char {:?} (U+{:04X}) at index {} of group {} (byte offset {})",
+                found, found as u32, char_in_group, group_index, byte_offset
+            ),
+            DecodeError::LengthMismatch { expected, actual } =>
+                write!(f, "Decoded data is {} bytes long, expected exactly {} bytes", actual, expected),
+            DecodeError::MalformedHeader =>
+                write!(f, "Decoded data does not start with a valid header of trimmed zero bytes"),
+            DecodeError::MissingChecksum =>
+                write!(f, "Decoded data is empty and does not contain a checksum"),
+            DecodeError::ChecksumMismatch { expected, actual } =>
+                write!(f, "checksum mismatch: expected {:#04x}, computed {:#04x}", expected, actual),
+            DecodeError::MisplacedPadding { c, position } => write!(
+                f, "padding char {:?} (U+{:04X}) at position {} is in a slot which must hold data",
+                c, c as u32, position
+            ),
+            DecodeError::IncompleteGroup { group_index, byte_offset } => write!(
+                f, "incomplete group {} at byte offset {}, input code points count is not a multiple of 4",
+                group_index, byte_offset
+            ),
+            DecodeError::SourceIo(ref e) => write!(f, "Failed to read from the source: {}", e),
+            DecodeError::DestIo(ref e) => write!(f, "Failed to write to the destination: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, Write};
+
+    use super::*;
+
+    struct Failing(io::ErrorKind);
+
+    impl Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> { Err(self.0.into()) }
+    }
+
+    impl Write for Failing {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> { Err(self.0.into()) }
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    fn encode_error(e: io::Error) -> Option<EncodeError> {
+        e.get_ref().and_then(|e| e.downcast_ref::<EncodeError>()).cloned()
+    }
+
+    fn decode_error(e: io::Error) -> Option<DecodeError> {
+        e.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()).cloned()
+    }
+
+    #[test]
+    fn test_encode_io_errors() {
+        let error = ::encode(&mut Failing(io::ErrorKind::WouldBlock), &mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(encode_error(error), Some(EncodeError::SourceIo(Arc::new(io::ErrorKind::WouldBlock.into()))));
+
+        let error = ::encode(&mut &b"abc"[..], &mut Failing(io::ErrorKind::BrokenPipe)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+        assert_eq!(encode_error(error), Some(EncodeError::DestIo(Arc::new(io::ErrorKind::BrokenPipe.into()))));
+    }
+
+    #[test]
+    fn test_decode_io_errors() {
+        let error = ::decode(&mut Failing(io::ErrorKind::TimedOut), &mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(decode_error(error), Some(DecodeError::SourceIo(Arc::new(io::ErrorKind::TimedOut.into()))));
+
+        let error = ::decode(&mut "👖📸🎈☕".as_bytes(), &mut Failing(io::ErrorKind::BrokenPipe)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+        let error = decode_error(error).unwrap();
+        assert_eq!(error, DecodeError::DestIo(Arc::new(io::ErrorKind::BrokenPipe.into())));
+        assert!(error.source().is_some());
+        assert_ne!(error, DecodeError::SourceIo(Arc::new(io::ErrorKind::BrokenPipe.into())));
+    }
+}
@@ -0,0 +1,44 @@
+//! Short emoji fingerprints of arbitrary data, available with the `sha2` feature.
+
+use sha2::{Digest, Sha256};
+
+use encode::encode_to_string;
+
+/// The number of bytes of the SHA-256 digest which are encoded into a fingerprint.
+///
+/// Ten bytes are exactly two groups, so a fingerprint is eight code points long and has no
+/// padding.
+pub const FINGERPRINT_BYTES: usize = 10;
+
+/// Computes a short, human-comparable fingerprint of the given data.
+///
+/// The fingerprint is the Ecoji encoding of the first [`FINGERPRINT_BYTES`](constant.FINGERPRINT_BYTES.html)
+/// bytes of the SHA-256 digest of the data, i.e. eight emojis. It is meant for visually
+/// comparing data, e.g. files or public keys, and not as a replacement of the full digest where
+/// collision resistance matters.
+///
+/// This function is only available with the `sha2` feature.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(ecoji::fingerprint(b"hello world"), "😰🎟💿👤🐅🦖📻🏿");
+/// assert_eq!(ecoji::fingerprint(b"hello world").chars().count(), 8);
+/// ```
+pub fn fingerprint(data: &[u8]) -> String {
+    let digest = Sha256::digest(data);
+    encode_to_string(&mut &digest[..FINGERPRINT_BYTES])
+        .expect("Reading from a slice cannot fail")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint() {
+        assert_eq!(fingerprint(b""), "🤼🚈🏞🖐🦳💒🏷🙆");
+        assert_eq!(fingerprint(b"input data"), "😝🔼💹🌝🎌🚒🎸😮");
+        assert_ne!(fingerprint(b"input data"), fingerprint(b"input data "));
+    }
+}
@@ -38,6 +38,11 @@
 //!   # }
 //!   # test().unwrap();
 //!   ```
+//!
+//!   Note that the concatenated encoded strings are generally not the same as the encoding of the
+//!   concatenated input, because every encoded string except the last one may end with padding.
+//!   They are the same only when the length of each input string except the last one is a
+//!   multiple of 5 bytes, since such strings are encoded without padding.
 //! * Data encoded with Ecoji has the same sorting order as the input data:
 //!
 //!   ```
@@ -72,6 +77,10 @@
 //!   # }
 //!   # test().unwrap();
 //!   ```
+//! * Decoding restores the exact length of the input, which does not have to be a multiple of
+//!   the group size: the padding of the last group determines how many of its bytes are data.
+//!   Therefore there is no need to store the length of the data separately, and trailing zero
+//!   bytes are preserved as well.
 //!
 //! ## Usage
 //!
@@ -102,6 +111,32 @@
 //! # }
 //! ```
 //!
+//! Finally, [`decode_groups`](fn.decode_groups.html) returns an iterator which yields decoded data
+//! one group (up to five bytes) at a time, as soon as each group is read from the source. This is
+//! useful for processing the decoded data incrementally, without holding all of it in memory.
+//!
+//! ## Alphabets
+//!
+//! By default, the alphabet of the reference Ecoji implementation is used. Some of its code
+//! points, like regional indicators or skin tone modifiers, may be displayed combined with their
+//! neighbours. If reliable rendering is more important than compatibility with other Ecoji
+//! implementations, data can be encoded with [`Alphabet::SAFE`](struct.Alphabet.html) instead,
+//! by using [`EncodeOptions`](struct.EncodeOptions.html) and
+//! [`DecodeOptions`](struct.DecodeOptions.html):
+//!
+//! ```
+//! use ecoji::{Alphabet, EncodeOptions, DecodeOptions};
+//!
+//! # fn test() -> ::std::io::Result<()> {
+//! let encoded = EncodeOptions::new().alphabet(Alphabet::SAFE).encode_to_string(&mut "data".as_bytes())?;
+//! let decoded = DecodeOptions::new().alphabet(Alphabet::SAFE).decode_to_string(&mut encoded.as_bytes())?;
+//!
+//! assert_eq!(decoded, "data");
+//! #   Ok(())
+//! # }
+//! # test().unwrap();
+//! ```
+//!
 //! ## Command line tool
 //!
 //! This crate also provides an executable binary, `ecoji`, which provides a command line
@@ -117,35 +152,290 @@
 //! systems). Run `ecoji --help` (assuming the aforementioned directory is in your `PATH`) to
 //! see documentation on how to invoke itl.
 //!
+//! The output of the tool is exactly the encoded or decoded data, without a trailing newline,
+//! and it never depends on the environment, e.g. on whether the standard output is a terminal.
+//! Scripts relying on byte-identical output, like golden tests, can pass `--deterministic` to
+//! state this explicitly.
+//!
+//! ## Minimal builds
+//!
+//! The only dependency of the library enabled by default is `phf`, which is used for fast lookup
+//! of code points when decoding. It is controlled by the `phf` feature; without it, decoding
+//! looks code points up with a binary search over the alphabet instead, and the crate has no
+//! external dependencies at all:
+//!
+//! ```toml
+//! [dependencies]
+//! ecoji = { version = "1.0.0", default-features = false }
+//! ```
+//!
+//! To avoid building `phf` and `phf_codegen` without giving up hash lookups, enable
+//! the `runtime-reverse-map` feature instead. It builds a standard `HashMap` for each alphabet
+//! the first time a code point of the alphabet is looked up, which is a one-time cost well under
+//! a millisecond; after that decoding is at least as fast as with `phf`. If `phf` is enabled as
+//! well, it takes precedence, so the default features must be disabled for this to have any
+//! effect:
+//!
+//! ```toml
+//! [dependencies]
+//! ecoji = { version = "1.0.0", default-features = false, features = ["runtime-reverse-map"] }
+//! ```
+//!
+//! ## Faster encoding
+//!
+//! The optional `fast-encode` feature trades space for speed: it adds a table with the UTF-8
+//! representation of every code point of each alphabet, about 16 KiB each, so that complete
+//! groups are encoded by copying bytes from the table instead of encoding every code point into
+//! UTF-8. This makes encoding random data about twice as fast; the `encode` benchmarks show the
+//! difference when run with and without the feature. The output is exactly the same.
+//!
+//! ## Fingerprints
+//!
+//! With the optional `sha2` feature, [`fingerprint`](fn.fingerprint.html) computes a short emoji
+//! fingerprint of arbitrary data: the first bytes of its SHA-256 digest encoded with Ecoji. Such
+//! fingerprints are easy to compare visually, e.g. when verifying a file or a public key.
+//!
+//! ## Parallel decoding
+//!
+//! With the optional `rayon` feature, [`decode_par`](fn.decode_par.html) decodes large strings
+//! in parallel. Since every group of four code points is decoded independently, the input is
+//! split on group boundaries and the parts are decoded on the rayon thread pool.
+//!
+//! ## Bit-level data
+//!
+//! With the optional `bitvec` feature, [`encode_bits`](fn.encode_bits.html) and
+//! [`decode_bits`](fn.decode_bits.html) encode sequences of bits whose length is not necessarily
+//! a multiple of eight, preserving their exact length. The `bitvec` crate is re-exported as
+//! `ecoji::bitvec`, so that the bit slices passed to these functions have matching types.
+//!
+//! ## Base64 conversion
+//!
+//! With the optional `base64` feature, [`base64_to_ecoji`](fn.base64_to_ecoji.html) and
+//! [`ecoji_to_base64`](fn.ecoji_to_base64.html) convert data between the two encodings directly,
+//! which is convenient when migrating stored data from one representation to the other.
+//!
 //! ## Issues and limitations
 //!
-//! Currently this crate does not provide an ability to do wrapping of the encoded text, like
-//! e.g. what the `base64` command does with the `-w` flag. It is possible that this feature will
-//! be implemented in future; pull requests for this functionality are welcome!
+//! The encoded text can be wrapped into lines of a fixed width, like what the `base64` command
+//! does with the `-w` flag, with [`encode_wrapped`](fn.encode_wrapped.html),
+//! [`encode_wrapped_with_offsets`](fn.encode_wrapped_with_offsets.html) and, for streams,
+//! [`encode_wrapped_stream`](fn.encode_wrapped_stream.html). Wrapped text, like the output of
+//! the reference tool, can be decoded directly, because line breaks are ignored when decoding.
 //!
 //! This library is almost a direct line-by-line reimplementation of the original algorithm
 //! which is implemented in Go. There were almost zero attempts at optimization, therefore
-//! performance characteristics may not be stellar. No benchmarking is done either. This is another
-//! area where contributions are very welcome.
+//! performance characteristics may not be stellar. Basic benchmarks of encoding and decoding
+//! can be run with `cargo bench`. This is another area where contributions are very welcome.
+//! Since data is read and written in small pieces, unbuffered sources and destinations should
+//! be wrapped into `BufReader` and `BufWriter`; [`CountingReader`](struct.CountingReader.html)
+//! and [`CountingWriter`](struct.CountingWriter.html) can show how many I/O calls are made.
 //!
 //! The core API of this library expects `std::io::Read` and `std::io::Write` instances. This
 //! implies that the only supported encoding for the emoji output is UTF-8.
+//!
+//! None of the code points of the Ecoji alphabets have canonical decompositions, so encoded data
+//! passes through systems applying NFC or NFD Unicode normalization unchanged, and no special
+//! handling of normalized input is needed. Compatibility normalization (NFKC or NFKD) is a
+//! different matter: it replaces some code points of the alphabet, like 🈂 or 🉐, with ordinary
+//! letters, which makes the data impossible to decode.
 
+extern crate ecoji_alphabet;
+#[cfg(feature = "phf")]
 extern crate phf;
+#[cfg(feature = "sha2")]
+extern crate sha2;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "bitvec")]
+pub extern crate bitvec;
+#[cfg(feature = "base64")]
+extern crate base64;
 #[cfg(test)] #[macro_use] extern crate quickcheck;
 
+mod alphabet;
 mod emojis;
 mod encode;
 mod decode;
 mod chars;
+mod error;
+mod options;
+mod counting;
+mod marker;
+mod checksum;
+mod trim;
+#[cfg(feature = "sha2")]
+mod fingerprint;
+#[cfg(feature = "rayon")]
+mod parallel;
+#[cfg(feature = "bitvec")]
+mod bits;
+#[cfg(feature = "base64")]
+mod convert;
 
-pub use encode::{encode, encode_to_string};
-pub use decode::{decode, decode_to_vec, decode_to_string};
+pub use encode::{encode, encode_limited, encode_to_string, encode_vectored, debug_encode_trace, Encoder, EncodingReader};
+pub use encode::{encode_and_flush, encode_to_string_cached, expected_padding, PaddingKind, PullEncoder};
+pub use encode::{encode_cancellable, encode_chars_to, encode_to_indices, encoded_chars, max_encoded_len, max_input_bytes};
+pub use encode::{encode_append, encode_channel, encode_cursor, encode_slice_to_string, encode_with_separators};
+pub use encode::{encode_tee, encode_wrapped, encode_wrapped_stream, encode_wrapped_with_offsets};
+pub use decode::{decode, decode_to_vec, decode_to_vec_limited, decode_to_string, decode_str, decoded_len, is_group_aligned};
+pub use decode::{decode_codepoints, decode_into_string, decode_to_string_streaming, decode_until_invalid, decode_utf16};
+pub use decode::{decode_from_byte_iter, decode_fixed_segments, decode_take, decode_with_hasher, decode_with_progress, ecoji_eq, shard};
+pub use decode::{char_offset_to_input_bytes, decode_exact, decode_from_indices, decode_repair_one, emoji_histogram};
+pub use decode::{group_byte_ranges, is_canonical, validate, validate_all};
+pub use decode::{decode_cursor, decode_groups, DecodeGroups, DecodedBytes, DecodedGroup, Decoder};
+pub use error::{EncodeError, DecodeError};
+pub use alphabet::{Alphabet, verify_reverse_map, MAX_EMOJI_UTF8_LEN, MIN_EMOJI_UTF8_LEN};
+pub use alphabet::{BITS_PER_CHAR, BYTES_PER_GROUP, CHARS_PER_GROUP};
+pub use options::{EncodeOptions, DecodeOptions, DEFAULT_BUFFER_SIZE};
+pub use counting::{CountingReader, CountingWriter, SinkWriter};
+pub use marker::{encode_with_marker, decode_with_marker, has_marker, MARKER};
+pub use checksum::{encode_with_checksum, decode_checked};
+pub use trim::{encode_trimmed, decode_trimmed, decode_trimmed_limited};
+#[cfg(feature = "sha2")]
+pub use fingerprint::{fingerprint, FINGERPRINT_BYTES};
+#[cfg(feature = "rayon")]
+pub use parallel::decode_par;
+#[cfg(feature = "bitvec")]
+pub use bits::{encode_bits, decode_bits};
+#[cfg(feature = "base64")]
+pub use convert::{base64_to_ecoji, ecoji_to_base64};
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    /// Parses a test vector input in the format of the reference implementation test data: pairs
+    /// of hex digits in either case, optionally separated by whitespace.
+    fn parse_hex(hex_input: &str) -> Vec<u8> {
+        let digits: Vec<u8> = hex_input.bytes()
+            .filter(|b| !b.is_ascii_whitespace())
+            .map(|b| (b as char).to_digit(16).unwrap_or_else(|| panic!("Invalid hex digit in {:?}", hex_input)) as u8)
+            .collect();
+        assert_eq!(digits.len() % 2, 0, "Odd number of hex digits in {:?}", hex_input);
+        digits.chunks(2).map(|d| d[0] << 4 | d[1]).collect()
+    }
+
+    /// Checks that the hex-encoded bytes encode to the expected emojis.
+    fn encode_reference_vector(hex_input: &str, expected_emoji: &str) {
+        let encoded = encode_to_string(&mut parse_hex(hex_input).as_slice()).unwrap();
+        assert_eq!(encoded, expected_emoji, "encoding {}", hex_input);
+    }
+
+    /// Checks that the emojis decode to the hex-encoded bytes.
+    fn decode_reference_vector(hex_input: &str, expected_emoji: &str) {
+        let decoded = decode_to_vec(&mut expected_emoji.as_bytes()).unwrap();
+        assert_eq!(decoded, parse_hex(hex_input), "decoding {}", expected_emoji);
+    }
+
+    #[test]
+    fn test_reference_vectors() {
+        let vectors = [
+            ("", ""),
+            ("61", "👕☕☕☕"),
+            ("616263", "👖📸🎈☕"),
+            ("696E707574206461 7461", "👶😲🇲👅🍉🔙🌥🌩"),
+            ("0001020304", "🀄🆚🍈🚀"),
+            ("61626364", "👖📸🎦⚜"),
+        ];
+
+        for &(hex_input, expected_emoji) in &vectors {
+            encode_reference_vector(hex_input, expected_emoji);
+            decode_reference_vector(hex_input, expected_emoji);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_preserves_length() {
+        let patterns: [fn(usize) -> u8; 4] = [
+            |_| 0x00,
+            |_| 0xff,
+            |i| (i * 37 + 11) as u8,
+            |i| if i % 2 == 0 { 0xff } else { 0x00 },
+        ];
+        for pattern in patterns.iter() {
+            for len in 0..=20 {
+                let input: Vec<u8> = (0..len).map(pattern).collect();
+                let encoded = encode_to_string(&mut input.as_slice()).unwrap();
+                assert_eq!(encoded.chars().count(), encoded_chars(len));
+
+                let output = decode_to_vec(&mut encoded.as_bytes()).unwrap();
+                assert_eq!(output.len(), len);
+                assert_eq!(output, input);
+                assert_eq!(decoded_len(&encoded), len);
+            }
+        }
+    }
+
+    /// Checks that encoding preserves the order of every pair of the inputs, with both alphabets.
+    fn check_sort_order(inputs: &[Vec<u8>]) {
+        for &alphabet in &[Alphabet::STANDARD, Alphabet::SAFE] {
+            let options = EncodeOptions::new().alphabet(alphabet);
+            let encoded: Vec<_> = inputs.iter()
+                .map(|input| options.encode_to_string(&mut input.as_slice()).unwrap())
+                .collect();
+
+            for (a, encoded_a) in inputs.iter().zip(&encoded) {
+                for (b, encoded_b) in inputs.iter().zip(&encoded) {
+                    assert_eq!(encoded_a.cmp(encoded_b), a.cmp(b), "comparing {:?} and {:?}", a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sort_order_of_last_byte() {
+        let base: Vec<u8> = (0..11).map(|i| i * 23 + 7).collect();
+        let mut inputs = Vec::new();
+        for len in 1..=base.len() {
+            for &last in &[0x00, 0x01, 0x03, 0x04, 0x7f, 0x80, 0xfc, 0xfe, 0xff] {
+                let mut input = base[..len].to_vec();
+                input[len - 1] = last;
+                inputs.push(input);
+            }
+        }
+        check_sort_order(&inputs);
+    }
+
+    #[test]
+    fn test_sort_order_of_prefixes() {
+        for &fill in &[0x00, 0x61, 0xff] {
+            let data: Vec<u8> = (0..16).map(|i| if i % 3 == 0 { fill } else { i * 17 }).collect();
+            let prefixes: Vec<_> = (0..=data.len()).map(|len| data[..len].to_vec()).collect();
+            check_sort_order(&prefixes);
+
+            // A prefix followed by zero bytes must still sort after the prefix itself
+            let padded: Vec<_> = (0..=10).map(|zeros| {
+                let mut input = data[..3].to_vec();
+                input.extend(vec![0; zeros]);
+                input
+            }).collect();
+            check_sort_order(&padded);
+        }
+    }
+
+    #[test]
+    fn test_sort_order_of_different_lengths() {
+        let mut inputs = Vec::new();
+        for len in 0..=11 {
+            inputs.push(vec![0x00; len]);
+            inputs.push(vec![0xff; len]);
+            inputs.push(vec![0x80; len]);
+        }
+        check_sort_order(&inputs);
+    }
+
+    #[test]
+    fn test_sort_order_of_extreme_bytes() {
+        // Every sequence of up to seven 0x00 and 0xff bytes
+        let mut inputs = Vec::new();
+        for len in 0..=7 {
+            for bits in 0..1u32 << len {
+                inputs.push((0..len).map(|i| if bits >> i & 1 == 1 { 0xff } else { 0x00 }).collect());
+            }
+        }
+        check_sort_order(&inputs);
+    }
+
     quickcheck! {
         fn encode_then_decode_identity(input: Vec<u8>) -> bool {
             let encoded = encode_to_string(&mut input.as_slice()).unwrap();
@@ -153,6 +443,14 @@ mod test {
             input == output
         }
 
+        fn decoded_len_matches_decoded_data(input: Vec<Vec<u8>>) -> bool {
+            let encoded: String = input.iter()
+                .map(|b| encode_to_string(&mut b.as_slice()).unwrap())
+                .collect();
+            let output = decode_to_vec(&mut encoded.as_bytes()).unwrap();
+            decoded_len(&encoded) == output.len()
+        }
+
         fn encoded_data_has_the_same_sort_order(input: Vec<Vec<u8>>) -> bool {
             // input          ---sort--->  input_sorted
             //
@@ -178,5 +476,50 @@ mod test {
 
             input_sorted == input2_sorted
         }
+
+        fn safe_alphabet_encode_then_decode_identity(input: Vec<u8>) -> bool {
+            let encoded = EncodeOptions::new().alphabet(Alphabet::SAFE)
+                .encode_to_string(&mut input.as_slice()).unwrap();
+            let output = DecodeOptions::new().alphabet(Alphabet::SAFE)
+                .decode_to_vec(&mut encoded.as_bytes()).unwrap();
+            input == output
+        }
+
+        fn safe_alphabet_encoded_data_has_the_same_sort_order(input: Vec<Vec<u8>>) -> bool {
+            let encode_options = EncodeOptions::new().alphabet(Alphabet::SAFE);
+            let decode_options = DecodeOptions::new().alphabet(Alphabet::SAFE);
+
+            let mut input_sorted = input.clone();
+            input_sorted.sort_unstable();
+
+            let mut output_sorted: Vec<_> = input.into_iter()
+                .map(|b| encode_options.encode_to_string(&mut b.as_slice()).unwrap())
+                .collect();
+            output_sorted.sort_unstable();
+
+            let input2_sorted: Vec<_> = output_sorted.into_iter()
+                .map(|s| decode_options.decode_to_vec(&mut s.as_bytes()).unwrap())
+                .collect();
+
+            input_sorted == input2_sorted
+        }
+
+        fn concatenation_of_aligned_encodings_is_encoding_of_concatenation(a: Vec<u8>, b: Vec<u8>) -> bool {
+            let a = &a[..a.len() / 5 * 5];
+
+            let encoded_a = encode_to_string(&mut &a[..]).unwrap();
+            let encoded_b = encode_to_string(&mut b.as_slice()).unwrap();
+            let encoded_ab = encode_to_string(&mut [a, &b].concat().as_slice()).unwrap();
+
+            encoded_a + &encoded_b == encoded_ab
+        }
+
+        fn concatenation_of_encodings_decodes_to_concatenation(a: Vec<u8>, b: Vec<u8>) -> bool {
+            let encoded = encode_to_string(&mut a.as_slice()).unwrap()
+                + &encode_to_string(&mut b.as_slice()).unwrap();
+            let output = decode_to_vec(&mut encoded.as_bytes()).unwrap();
+
+            output == [a, b].concat()
+        }
     }
 }
@@ -0,0 +1,138 @@
+//! Opt-in framing of encoded data with a leading marker.
+//!
+//! The first code point of every group produced by the encoder carries data, so encoded data
+//! never starts with a padding code point. A group of four paddings is therefore a marker which
+//! cannot be confused with the beginning of encoded data, and which lets consumers recognize
+//! Ecoji data embedded among other content. Marked data is not a plain Ecoji stream, so it must
+//! be decoded with [`decode_with_marker`](fn.decode_with_marker.html).
+
+use std::io::{self, Read, Write};
+
+use decode::decode;
+use encode::encode;
+use error::{DecodeError, EncodeError};
+
+/// The marker written in front of the data by [`encode_with_marker`](fn.encode_with_marker.html):
+/// a group of four padding code points.
+pub const MARKER: &str = "\u{2615}\u{2615}\u{2615}\u{2615}";
+
+/// Checks whether the given bytes start with the [`MARKER`](constant.MARKER.html).
+///
+/// # Examples
+///
+/// ```
+/// assert!(ecoji::has_marker("☕☕☕☕👶😲🇲👅🍉🔙🌥🌩".as_bytes()));
+/// assert!(!ecoji::has_marker("👶😲🇲👅🍉🔙🌥🌩".as_bytes()));
+/// ```
+pub fn has_marker(data: &[u8]) -> bool {
+    data.starts_with(MARKER.as_bytes())
+}
+
+/// Writes the [`MARKER`](constant.MARKER.html) to the provided destination, then encodes the
+/// entire source into the Ecoji format and writes a UTF-8 representation of the encoded data
+/// after it.
+///
+/// If successful, returns the number of bytes which were written to the destination writer,
+/// including the marker.
+///
+/// Failure conditions are exactly the same as those of the [`encode`](fn.encode.html) function.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let mut output: Vec<u8> = Vec::new();
+/// ecoji::encode_with_marker(&mut "input data".as_bytes(), &mut output)?;
+///
+/// assert_eq!(output, "☕☕☕☕👶😲🇲👅🍉🔙🌥🌩".as_bytes());
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn encode_with_marker<R: Read + ?Sized, W: Write + ?Sized>(source: &mut R, destination: &mut W) -> io::Result<usize> {
+    destination.write_all(MARKER.as_bytes()).map_err(EncodeError::wrap_destination)?;
+    Ok(MARKER.len() + encode(source, destination)?)
+}
+
+/// Verifies that the source starts with the [`MARKER`](constant.MARKER.html), then decodes the
+/// rest of the source from the Ecoji format and writes the result of the decoding to the provided
+/// destination.
+///
+/// If successful, returns the number of bytes which were written to the destination writer.
+///
+/// In addition to the [`decode`](fn.decode.html) failure conditions, this function returns an
+/// error of the `InvalidData` kind wrapping
+/// [`DecodeError::MissingMarker`](enum.DecodeError.html) if the source does not start with the
+/// marker. Nothing is written to the destination in this case.
+///
+/// # Examples
+///
+/// ```
+/// use ecoji::DecodeError;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let mut output: Vec<u8> = Vec::new();
+/// ecoji::decode_with_marker(&mut "☕☕☕☕👶😲🇲👅🍉🔙🌥🌩".as_bytes(), &mut output)?;
+/// assert_eq!(output, b"input data");
+///
+/// let error = ecoji::decode_with_marker(&mut "👶😲🇲👅🍉🔙🌥🌩".as_bytes(), &mut Vec::new()).unwrap_err();
+/// assert_eq!(
+///     error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+///     Some(&DecodeError::MissingMarker)
+/// );
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn decode_with_marker<R: Read + ?Sized, W: Write + ?Sized>(source: &mut R, destination: &mut W) -> io::Result<usize> {
+    let mut marker = [0; 12];
+    match source.read_exact(&mut marker) {
+        Ok(()) if has_marker(&marker) => {}
+        Ok(()) => return Err(DecodeError::MissingMarker.into_io()),
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(DecodeError::MissingMarker.into_io()),
+        Err(e) => return Err(DecodeError::wrap_source(e)),
+    }
+
+    decode(source, destination)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alphabet::Alphabet;
+
+    #[test]
+    fn test_marker_is_not_encoder_output() {
+        assert_eq!(MARKER.len(), 12);
+        for alphabet in &[Alphabet::STANDARD, Alphabet::SAFE] {
+            assert!(MARKER.chars().all(|c| c == alphabet.padding()));
+        }
+    }
+
+    #[test]
+    fn test_marker_round_trip() {
+        let data: Vec<u8> = (0..32).collect();
+        for len in 0..data.len() {
+            let mut encoded = Vec::new();
+            let written = encode_with_marker(&mut &data[..len], &mut encoded).unwrap();
+            assert_eq!(written, encoded.len());
+            assert!(has_marker(&encoded));
+
+            let mut decoded = Vec::new();
+            decode_with_marker(&mut encoded.as_slice(), &mut decoded).unwrap();
+            assert_eq!(decoded, &data[..len]);
+        }
+    }
+
+    #[test]
+    fn test_missing_marker() {
+        for input in &["", "☕☕☕", "☕☕☕👶", "👶😲🇲👅🍉🔙🌥🌩"] {
+            let error = decode_with_marker(&mut input.as_bytes(), &mut Vec::new()).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(
+                error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+                Some(&DecodeError::MissingMarker)
+            );
+        }
+    }
+}
@@ -0,0 +1,589 @@
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+use alphabet::Alphabet;
+use decode::{decode_with, Decoder};
+use encode::{encode_up_to, encode_to_string_with, Encoder, EncodingReader, PullEncoder};
+use error::{DecodeError, EncodeError};
+
+/// The recommended size of the staging buffers set with
+/// [`EncodeOptions::buffer_size`](struct.EncodeOptions.html#method.buffer_size) and
+/// [`DecodeOptions::buffer_size`](struct.DecodeOptions.html#method.buffer_size), in bytes.
+///
+/// It is the same as the default capacity of `BufReader` and `BufWriter`, and holds 1638
+/// complete input groups when encoding.
+pub const DEFAULT_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Options and flags which can be used to configure how data is encoded.
+///
+/// The default options produce exactly the same output as the [`encode`](fn.encode.html) family
+/// of functions.
+///
+/// # Examples
+///
+/// ```
+/// use ecoji::{Alphabet, EncodeOptions};
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let options = EncodeOptions::new().alphabet(Alphabet::SAFE);
+/// let output = options.encode_to_string(&mut "input data".as_bytes())?;
+///
+/// assert_eq!(output, "💕🚁🈺👤🍣🔸🌿🍃");
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct EncodeOptions {
+    alphabet: Alphabet,
+    buffer_size: usize,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> EncodeOptions {
+        EncodeOptions {
+            alphabet: Alphabet::STANDARD,
+            buffer_size: 0,
+        }
+    }
+}
+
+impl EncodeOptions {
+    /// Creates a new set of options with the default configuration.
+    pub fn new() -> EncodeOptions {
+        EncodeOptions::default()
+    }
+
+    /// Sets the alphabet to encode data with; [`Alphabet::STANDARD`](struct.Alphabet.html) by
+    /// default.
+    pub fn alphabet(mut self, alphabet: Alphabet) -> EncodeOptions {
+        self.alphabet = alphabet;
+        self
+    }
+
+    /// Sets the size of the staging buffers wrapping the source and the destination of
+    /// [`encode`](#method.encode) and [`encode_to_string`](#method.encode_to_string), in bytes;
+    /// 0 by default, which means that no buffers are used.
+    ///
+    /// Encoding reads and writes one group at a time, so for unbuffered sources and destinations,
+    /// like files or sockets, larger buffers trade memory for fewer I/O calls;
+    /// [`DEFAULT_BUFFER_SIZE`](constant.DEFAULT_BUFFER_SIZE.html) is a sensible choice for large
+    /// streams. The buffered source may be read past the end of the encoded data, and the
+    /// destination is not flushed, exactly like without buffers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecoji::{CountingReader, EncodeOptions, DEFAULT_BUFFER_SIZE};
+    ///
+    /// # fn test() -> ::std::io::Result<()> {
+    /// let data = vec![0xa5; 4096];
+    /// let mut source = CountingReader::new(data.as_slice());
+    ///
+    /// let options = EncodeOptions::new().buffer_size(DEFAULT_BUFFER_SIZE);
+    /// options.encode(&mut source, &mut Vec::new())?;
+    /// // Without the buffer, every group of five bytes would be a separate call
+    /// assert!(source.calls() <= 3);
+    /// #  Ok(())
+    /// # }
+    /// # test().unwrap();
+    /// ```
+    pub fn buffer_size(mut self, buffer_size: usize) -> EncodeOptions {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Encodes the entire source with these options and writes a UTF-8 representation of
+    /// the encoded data to the provided destination.
+    ///
+    /// Behaves like the [`encode`](fn.encode.html) function otherwise.
+    pub fn encode<R: Read + ?Sized, W: Write + ?Sized>(&self, source: &mut R, destination: &mut W) -> io::Result<usize> {
+        if self.buffer_size == 0 {
+            return encode_up_to(&self.alphabet, source, destination, None);
+        }
+
+        let mut source = BufReader::with_capacity(self.buffer_size, source);
+        let mut destination = BufWriter::with_capacity(self.buffer_size, destination);
+        let bytes_written = encode_up_to(&self.alphabet, &mut source, &mut destination, None)?;
+        destination.into_inner().map_err(|e| EncodeError::wrap_destination(e.into_error()))?;
+        Ok(bytes_written)
+    }
+
+    /// Encodes the entire source with these options, storing the result of the encoding to
+    /// a new owned string.
+    ///
+    /// Behaves like the [`encode_to_string`](fn.encode_to_string.html) function otherwise.
+    pub fn encode_to_string<R: Read + ?Sized>(&self, source: &mut R) -> io::Result<String> {
+        if self.buffer_size == 0 {
+            encode_to_string_with(&self.alphabet, source)
+        } else {
+            encode_to_string_with(&self.alphabet, &mut BufReader::with_capacity(self.buffer_size, source))
+        }
+    }
+
+    /// Creates a new [`Encoder`](struct.Encoder.html) which encodes data with these options and
+    /// writes it to the given writer.
+    pub fn encoder<W: Write>(&self, inner: W) -> Encoder<W> {
+        Encoder::with_alphabet(&self.alphabet, inner)
+    }
+
+    /// Creates a new [`EncodingReader`](struct.EncodingReader.html) which encodes data read from
+    /// the given reader with these options.
+    pub fn encoding_reader<R: Read>(&self, inner: R) -> EncodingReader<R> {
+        EncodingReader::with_alphabet(&self.alphabet, inner)
+    }
+
+    /// Creates a new [`PullEncoder`](struct.PullEncoder.html) which encodes data with these
+    /// options.
+    pub fn pull_encoder(&self) -> PullEncoder {
+        PullEncoder::with_alphabet(&self.alphabet)
+    }
+}
+
+/// Options and flags which can be used to configure how data is decoded.
+///
+/// The default options make decoding behave exactly like the [`decode`](fn.decode.html) family
+/// of functions.
+///
+/// # Examples
+///
+/// ```
+/// use ecoji::{Alphabet, DecodeOptions};
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let options = DecodeOptions::new().alphabet(Alphabet::SAFE);
+/// let output = options.decode_to_string(&mut "💕🚁🈺👤🍣🔸🌿🍃".as_bytes())?;
+///
+/// assert_eq!(output, "input data");
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct DecodeOptions {
+    pub(crate) alphabet: Alphabet,
+    pub(crate) ignore_chars: Vec<char>,
+    pub(crate) pad_incomplete: bool,
+    pub(crate) delimiter: Option<char>,
+    pub(crate) strip_skin_tones: bool,
+    buffer_size: usize,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> DecodeOptions {
+        DecodeOptions {
+            alphabet: Alphabet::STANDARD,
+            ignore_chars: Vec::new(),
+            pad_incomplete: false,
+            delimiter: None,
+            strip_skin_tones: false,
+            buffer_size: 0,
+        }
+    }
+}
+
+impl DecodeOptions {
+    /// Creates a new set of options with the default configuration.
+    pub fn new() -> DecodeOptions {
+        DecodeOptions::default()
+    }
+
+    /// Sets the alphabet to decode data with; [`Alphabet::STANDARD`](struct.Alphabet.html) by
+    /// default.
+    pub fn alphabet(mut self, alphabet: Alphabet) -> DecodeOptions {
+        self.alphabet = alphabet;
+        self
+    }
+
+    /// Sets the code points which are skipped when decoding; none by default.
+    ///
+    /// This is useful for data which went through systems inserting invisible characters
+    /// between emojis, like zero-width joiners (U+200D) or zero-width spaces (U+200B). Line
+    /// breaks are always skipped, regardless of this option. Code points of the alphabet
+    /// should not be ignored, since they would be dropped from the data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecoji::DecodeOptions;
+    ///
+    /// # fn test() -> ::std::io::Result<()> {
+    /// let options = DecodeOptions::new().ignore_chars(&['\u{200D}', '\u{200B}', ' ']);
+    /// let output = options.decode_to_string(&mut "👶\u{200D}😲🇲 👅🍉\u{200B}🔙🌥🌩".as_bytes())?;
+    ///
+    /// assert_eq!(output, "input data");
+    /// #  Ok(())
+    /// # }
+    /// # test().unwrap();
+    /// ```
+    pub fn ignore_chars(mut self, chars: &[char]) -> DecodeOptions {
+        self.ignore_chars = chars.to_vec();
+        self
+    }
+
+    /// Sets whether skin tone modifiers (U+1F3FB to U+1F3FF) which are not code points of
+    /// the alphabet are skipped when decoding; `false` by default.
+    ///
+    /// Some keyboards and apps append a skin tone modifier to emojis which can take one, so
+    /// encoded data copied from them contains sequences like `👍🏽` instead of the base emoji
+    /// alone. With this option, the modifiers are stripped and the base emoji decodes to its own
+    /// index, as if the modifiers were listed in [`ignore_chars`](#method.ignore_chars).
+    ///
+    /// Modifiers which are code points of the alphabet are data and are never skipped. This is
+    /// the case for [`Alphabet::STANDARD`](struct.Alphabet.html), so the option has no effect with
+    /// it: a modifier appended to an emoji of the standard alphabet cannot be told apart from one
+    /// encoding data. [`Alphabet::SAFE`](struct.Alphabet.html#associatedconstant.SAFE) excludes
+    /// the modifiers, so data encoded with it can be recovered this way.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecoji::{Alphabet, DecodeOptions, EncodeOptions};
+    ///
+    /// # fn test() -> ::std::io::Result<()> {
+    /// let encoded = EncodeOptions::new().alphabet(Alphabet::SAFE).encode_to_string(&mut "Wave".as_bytes())?;
+    /// assert!(encoded.starts_with('👍'));
+    ///
+    /// // An app sent the encoded data with a medium skin tone on the thumbs up
+    /// let copied = encoded.replace('👍', "👍🏽");
+    ///
+    /// let options = DecodeOptions::new().alphabet(Alphabet::SAFE);
+    /// assert!(options.decode_to_vec(&mut copied.as_bytes()).is_err());
+    ///
+    /// let options = options.strip_skin_tones(true);
+    /// assert_eq!(options.decode_to_string(&mut copied.as_bytes())?, "Wave");
+    /// #  Ok(())
+    /// # }
+    /// # test().unwrap();
+    /// ```
+    pub fn strip_skin_tones(mut self, strip_skin_tones: bool) -> DecodeOptions {
+        self.strip_skin_tones = strip_skin_tones;
+        self
+    }
+
+    /// Sets whether an incomplete group at the end of the input is decoded as if it was padded;
+    /// `false` by default, in which case such input is rejected with an error of the
+    /// `UnexpectedEof` kind.
+    ///
+    /// This is meant for lenient importing of truncated data. Note that it loses data silently:
+    /// a truncated group yields only as many bytes as its remaining code points fully cover, i.e.
+    /// one, two or three bytes for one, two or three code points, and everything the truncated
+    /// part of the group carried is dropped. The output does not indicate that this happened, so
+    /// truncated data cannot be told apart from complete data of the same length.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecoji::DecodeOptions;
+    ///
+    /// # fn test() -> ::std::io::Result<()> {
+    /// let truncated = "👶😲🇲👅🍉🔙";
+    /// assert!(DecodeOptions::new().decode_to_vec(&mut truncated.as_bytes()).is_err());
+    ///
+    /// let options = DecodeOptions::new().pad_incomplete(true);
+    /// assert_eq!(options.decode_to_vec(&mut truncated.as_bytes())?, b"input d");
+    /// #  Ok(())
+    /// # }
+    /// # test().unwrap();
+    /// ```
+    pub fn pad_incomplete(mut self, pad_incomplete: bool) -> DecodeOptions {
+        self.pad_incomplete = pad_incomplete;
+        self
+    }
+
+    /// Sets the delimiter which separates groups in the input; `None` by default.
+    ///
+    /// When set, every group except the last one must be followed by exactly one delimiter,
+    /// e.g. for data stored as comma-separated fields of one group each. A trailing delimiter
+    /// after the last group is allowed. Any other code point following a group is rejected with
+    /// an error of the `InvalidData` kind wrapping
+    /// [`DecodeError::MissingDelimiter`](enum.DecodeError.html). Line breaks and code points set
+    /// with [`ignore_chars`](#method.ignore_chars) are still skipped around delimiters.
+    ///
+    /// The delimiter may itself be a line break or an ignored code point, e.g. `'\n'` for data
+    /// stored as one group per line. Such a delimiter is matched after a group before it is
+    /// skipped, so it is still required between groups; any other occurrences of it are skipped as
+    /// usual, which allows e.g. empty lines.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ecoji::DecodeOptions;
+    ///
+    /// # fn test() -> ::std::io::Result<()> {
+    /// let options = DecodeOptions::new().delimiter(Some(','));
+    /// let output = options.decode_to_string(&mut "👶😲🇲👅,🍉🔙🌥🌩".as_bytes())?;
+    ///
+    /// assert_eq!(output, "input data");
+    /// assert!(options.decode_to_string(&mut "👶😲🇲👅🍉🔙🌥🌩".as_bytes()).is_err());
+    /// #  Ok(())
+    /// # }
+    /// # test().unwrap();
+    /// ```
+    pub fn delimiter(mut self, delimiter: Option<char>) -> DecodeOptions {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Sets the size of the staging buffers wrapping the source and the destination of
+    /// [`decode`](#method.decode) and the other decoding methods, in bytes; 0 by default, which
+    /// means that no buffers are used.
+    ///
+    /// Decoding reads one code point and writes one group at a time, so for unbuffered sources
+    /// and destinations, like files or sockets, larger buffers trade memory for fewer I/O calls;
+    /// [`DEFAULT_BUFFER_SIZE`](constant.DEFAULT_BUFFER_SIZE.html) is a sensible choice for large
+    /// streams. The destination is not flushed, exactly like without buffers.
+    pub fn buffer_size(mut self, buffer_size: usize) -> DecodeOptions {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Decodes the entire source with these options and writes the result of the decoding to
+    /// the provided destination.
+    ///
+    /// Behaves like the [`decode`](fn.decode.html) function otherwise.
+    pub fn decode<R: Read + ?Sized, W: Write + ?Sized>(&self, source: &mut R, destination: &mut W) -> io::Result<usize> {
+        if self.buffer_size == 0 {
+            return decode_with(self, source, destination);
+        }
+
+        let mut source = BufReader::with_capacity(self.buffer_size, source);
+        let mut destination = BufWriter::with_capacity(self.buffer_size, destination);
+        let bytes_written = decode_with(self, &mut source, &mut destination)?;
+        destination.into_inner().map_err(|e| DecodeError::wrap_destination(e.into_error()))?;
+        Ok(bytes_written)
+    }
+
+    /// Decodes the entire source with these options, storing the result of the decoding to
+    /// a new byte vector.
+    ///
+    /// Behaves like the [`decode_to_vec`](fn.decode_to_vec.html) function otherwise.
+    pub fn decode_to_vec<R: Read + ?Sized>(&self, source: &mut R) -> io::Result<Vec<u8>> {
+        let mut output = Vec::new();
+        self.decode(source, &mut output)?;
+        Ok(output)
+    }
+
+    /// Decodes the entire source with these options, storing the result of the decoding to
+    /// a new owned string.
+    ///
+    /// Behaves like the [`decode_to_string`](fn.decode_to_string.html) function otherwise.
+    pub fn decode_to_string<R: Read + ?Sized>(&self, source: &mut R) -> io::Result<String> {
+        let output = self.decode_to_vec(source)?;
+        String::from_utf8(output).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Creates a new [`Decoder`](struct.Decoder.html) which decodes data read from the given
+    /// reader with these options.
+    pub fn decoder<R: Read>(&self, inner: R) -> Decoder<R> {
+        Decoder::with_options(self, inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use counting::{CountingReader, CountingWriter};
+
+    #[test]
+    fn test_default_options_match_functions() {
+        let input = b"some input data";
+
+        let encoded = EncodeOptions::new().encode_to_string(&mut &input[..]).unwrap();
+        assert_eq!(encoded, ::encode_to_string(&mut &input[..]).unwrap());
+
+        let decoded = DecodeOptions::new().decode_to_vec(&mut encoded.as_bytes()).unwrap();
+        assert_eq!(decoded, input);
+    }
+
+    #[test]
+    fn test_alphabets_are_not_interchangeable() {
+        let input = b"\x00\x01\x02\x03\x04";
+
+        let safe = EncodeOptions::new().alphabet(Alphabet::SAFE).encode_to_string(&mut &input[..]).unwrap();
+        assert_ne!(safe, ::encode_to_string(&mut &input[..]).unwrap());
+        assert_ne!(DecodeOptions::new().decode_to_vec(&mut safe.as_bytes()).ok(), Some(input.to_vec()));
+        assert_eq!(DecodeOptions::new().alphabet(Alphabet::SAFE).decode_to_vec(&mut safe.as_bytes()).unwrap(), input);
+    }
+
+    #[test]
+    fn test_pull_encoder_with_options() {
+        let options = EncodeOptions::new().alphabet(Alphabet::SAFE);
+        let mut encoder = options.pull_encoder();
+        encoder.push(b"some input data");
+        encoder.end();
+
+        let output: String = ::std::iter::from_fn(|| encoder.next_char()).collect();
+        assert_eq!(output, options.encode_to_string(&mut &b"some input data"[..]).unwrap());
+    }
+
+    #[test]
+    fn test_encoding_reader_with_options() {
+        let options = EncodeOptions::new().alphabet(Alphabet::SAFE);
+        let mut output = String::new();
+        options.encoding_reader(&b"some input data"[..]).read_to_string(&mut output).unwrap();
+
+        assert_eq!(output, options.encode_to_string(&mut &b"some input data"[..]).unwrap());
+    }
+
+    #[test]
+    fn test_strip_skin_tones() {
+        let input = b"some input data";
+        let encoded = EncodeOptions::new().alphabet(Alphabet::SAFE).encode_to_string(&mut &input[..]).unwrap();
+        let modifiers = ['\u{1F3FB}', '\u{1F3FC}', '\u{1F3FD}', '\u{1F3FE}', '\u{1F3FF}'];
+        let modified: String = encoded.chars()
+            .zip(modifiers.iter().cycle())
+            .flat_map(|(c, &m)| vec![c, m])
+            .collect();
+
+        let options = DecodeOptions::new().alphabet(Alphabet::SAFE);
+        assert!(options.decode_to_vec(&mut modified.as_bytes()).is_err());
+
+        let options = options.strip_skin_tones(true);
+        assert_eq!(options.decode_to_vec(&mut modified.as_bytes()).unwrap(), input);
+
+        // each base emoji decodes to its own index
+        for i in 0..1024 {
+            let c = Alphabet::SAFE.emoji(i);
+            let group: String = [c, '\u{1F3FD}', c, c, '\u{1F3FB}', c].iter().collect();
+            let expected = options.decode_to_vec(&mut [c; 4].iter().collect::<String>().as_bytes()).unwrap();
+            assert_eq!(options.decode_to_vec(&mut group.as_bytes()).unwrap(), expected);
+        }
+
+        // in the standard alphabet, the modifiers are data
+        let encoded = ::encode_to_string(&mut &input[..]).unwrap();
+        let options = DecodeOptions::new().strip_skin_tones(true);
+        assert_eq!(options.decode_to_vec(&mut encoded.as_bytes()).unwrap(), input);
+        let standard: String = (0..4).map(|_| '\u{1F3FB}').collect();
+        assert_eq!(options.decode_to_vec(&mut standard.as_bytes()).unwrap(), ::decode_str(&standard).unwrap());
+    }
+
+    #[test]
+    fn test_ignore_chars() {
+        let input = b"some input data";
+        let encoded = ::encode_to_string(&mut &input[..]).unwrap();
+        let joined = encoded.chars().map(|c| c.to_string()).collect::<Vec<_>>().join("\u{200D}");
+
+        assert!(DecodeOptions::new().decode_to_vec(&mut joined.as_bytes()).is_err());
+
+        let options = DecodeOptions::new().ignore_chars(&['\u{200D}']);
+        assert_eq!(options.decode_to_vec(&mut joined.as_bytes()).unwrap(), input);
+
+        let mut decoded = Vec::new();
+        options.decoder(joined.as_bytes()).read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, input);
+
+        // characters which are not explicitly ignored are still rejected
+        let spaced = format!("{}\u{200B}", joined);
+        assert!(options.decode_to_vec(&mut spaced.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_pad_incomplete() {
+        let input: Vec<u8> = (0..10).map(|i| i * 25).collect();
+        let encoded: Vec<char> = ::encode_to_string(&mut &input[..]).unwrap().chars().collect();
+        let options = DecodeOptions::new().pad_incomplete(true);
+
+        for (chars, bytes) in [(4, 5), (5, 6), (6, 7), (7, 8), (8, 10)] {
+            let truncated: String = encoded[..chars].iter().collect();
+            assert_eq!(options.decode_to_vec(&mut truncated.as_bytes()).unwrap(), &input[..bytes]);
+
+            let mut decoded = Vec::new();
+            options.decoder(truncated.as_bytes()).read_to_end(&mut decoded).unwrap();
+            assert_eq!(decoded, &input[..bytes]);
+        }
+
+        // padded groups are decoded the same with and without the option
+        let padded = ::encode_to_string(&mut &input[..8]).unwrap();
+        assert_eq!(options.decode_to_vec(&mut padded.as_bytes()).unwrap(), &input[..8]);
+
+        // invalid code points are still rejected
+        assert!(options.decode_to_vec(&mut "👶★".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_delimiter() {
+        let input: Vec<u8> = (0..23).collect();
+        let encoded: Vec<char> = ::encode_to_string(&mut &input[..]).unwrap().chars().collect();
+        let groups: Vec<String> = encoded.chunks(4).map(|g| g.iter().collect()).collect();
+        let options = DecodeOptions::new().delimiter(Some(','));
+
+        for delimited in &[groups.join(","), groups.join(",") + ",", groups.join(",\n")] {
+            assert_eq!(options.decode_to_vec(&mut delimited.as_bytes()).unwrap(), input);
+
+            let mut decoded = Vec::new();
+            options.decoder(delimited.as_bytes()).read_to_end(&mut decoded).unwrap();
+            assert_eq!(decoded, input);
+        }
+
+        let error = options.decode_to_vec(&mut groups.concat().as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+            Some(&DecodeError::MissingDelimiter { delimiter: ',', c: encoded[4], position: 4 })
+        );
+
+        // doubled delimiters are not skipped
+        assert!(options.decode_to_vec(&mut groups.join(",,").as_bytes()).is_err());
+
+        // line breaks and ignored code points can be delimiters too
+        let ignoring = DecodeOptions::new().ignore_chars(&['|']);
+        for &(ref options, delimiter) in &[(DecodeOptions::new(), '\n'), (ignoring, '|')] {
+            let options = options.clone().delimiter(Some(delimiter));
+            let separator = delimiter.to_string();
+            for delimited in &[groups.join(&separator), groups.join(&separator) + &separator,
+                               groups.join(&(separator.clone() + &separator))] {
+                assert_eq!(options.decode_to_vec(&mut delimited.as_bytes()).unwrap(), input);
+            }
+
+            let error = options.decode_to_vec(&mut groups.concat().as_bytes()).unwrap_err();
+            assert_eq!(
+                error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+                Some(&DecodeError::MissingDelimiter { delimiter, c: encoded[4], position: 4 })
+            );
+        }
+        let crlf = DecodeOptions::new().delimiter(Some('\n'));
+        assert_eq!(crlf.decode_to_vec(&mut groups.join("\r\n").as_bytes()).unwrap(), input);
+    }
+
+    #[test]
+    fn test_streaming_with_options() {
+        let input = b"some input data";
+        let (encode_options, decode_options) = (
+            EncodeOptions::new().alphabet(Alphabet::SAFE),
+            DecodeOptions::new().alphabet(Alphabet::SAFE),
+        );
+
+        let mut encoder = encode_options.encoder(Vec::new());
+        encoder.write_all(input).unwrap();
+        let encoded = encoder.finish().unwrap();
+        assert_eq!(encoded, encode_options.encode_to_string(&mut &input[..]).unwrap().as_bytes());
+
+        let mut decoded = Vec::new();
+        decode_options.decoder(encoded.as_slice()).read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, input);
+    }
+
+    #[test]
+    fn test_buffer_size() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i * 7) as u8).collect();
+        let encoded = ::encode_to_string(&mut input.as_slice()).unwrap();
+
+        let options = EncodeOptions::new().buffer_size(DEFAULT_BUFFER_SIZE);
+        let mut source = CountingReader::new(input.as_slice());
+        let mut destination = CountingWriter::new(Vec::new());
+        assert_eq!(options.encode(&mut source, &mut destination).unwrap(), encoded.len());
+        assert_eq!(destination.get_ref(), encoded.as_bytes());
+        assert!(source.calls() <= 3);
+        assert!(destination.calls() <= (encoded.len() / DEFAULT_BUFFER_SIZE + 1) as u64);
+        assert_eq!(options.encode_to_string(&mut input.as_slice()).unwrap(), encoded);
+
+        let options = DecodeOptions::new().buffer_size(DEFAULT_BUFFER_SIZE);
+        let mut source = CountingReader::new(encoded.as_bytes());
+        let mut destination = CountingWriter::new(Vec::new());
+        assert_eq!(options.decode(&mut source, &mut destination).unwrap(), input.len());
+        assert_eq!(destination.get_ref(), &input);
+        assert!(source.calls() <= (encoded.len() / DEFAULT_BUFFER_SIZE + 2) as u64);
+        assert!(destination.calls() <= (input.len() / DEFAULT_BUFFER_SIZE + 1) as u64);
+        assert_eq!(options.decode_to_vec(&mut encoded.as_bytes()).unwrap(), input);
+    }
+}
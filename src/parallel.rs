@@ -0,0 +1,126 @@
+//! Parallel decoding of large documents, available with the `rayon` feature.
+
+use std::io;
+
+use rayon::prelude::*;
+
+use decode::{decode_str, shard};
+use error::DecodeError;
+
+/// Decodes an Ecoji-encoded string in parallel, storing the result of the decoding to a new byte
+/// vector.
+///
+/// The input is split on group boundaries with [`shard`](fn.shard.html) into one shard for each
+/// thread of the current rayon thread pool, the shards are decoded in parallel, and the results
+/// are concatenated. The result is exactly the same as that of [`decode_str`](fn.decode_str.html);
+/// splitting the input requires a pass over its code points, so this only pays off for large
+/// inputs.
+///
+/// Failure conditions are exactly the same as those of the [`decode`](fn.decode.html) function.
+/// If the input contains several errors, the one closest to its beginning is returned, and
+/// positions in errors are relative to the whole input.
+///
+/// This function is only available with the `rayon` feature.
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let input = ecoji::encode_to_string(&mut "input data".repeat(1000).as_bytes())?;
+/// let output = ecoji::decode_par(&input)?;
+///
+/// assert_eq!(output, "input data".repeat(1000).as_bytes());
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn decode_par(input: &str) -> io::Result<Vec<u8>> {
+    let shards = shard(input, rayon::current_num_threads())?;
+    let results: Vec<_> = shards.par_iter().map(|shard| decode_str(shard)).collect();
+
+    let mut output = Vec::with_capacity(results.iter().map(|r| r.as_ref().map_or(0, Vec::len)).sum());
+    let mut position = 0;
+    for (shard, result) in shards.iter().zip(results) {
+        match result {
+            Ok(decoded) => output.extend_from_slice(&decoded),
+            Err(e) => return Err(offset_error(e, position)),
+        }
+        position += shard.chars().count() as u64;
+    }
+
+    Ok(output)
+}
+
+/// Makes the position of a decoding error of a shard relative to the whole input, given the
+/// number of code points preceding the shard.
+fn offset_error(e: io::Error, offset: u64) -> io::Error {
+    match e.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()) {
+        Some(&DecodeError::NotInAlphabet { c, position }) =>
+            DecodeError::NotInAlphabet { c, position: offset + position }.into_io(),
+        Some(&DecodeError::MisplacedPadding { c, position }) =>
+            DecodeError::MisplacedPadding { c, position: offset + position }.into_io(),
+        _ => e,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decodes the input in a pool of several threads, so that it is split into several shards
+    /// regardless of the number of CPUs.
+    fn decode_par_sharded(input: &str) -> io::Result<Vec<u8>> {
+        rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap().install(|| decode_par(input))
+    }
+
+    #[test]
+    fn test_decode_par() {
+        let data: Vec<u8> = (0..100_003u32).map(|i| (i * 7 % 251) as u8).collect();
+        let encoded = ::encode_to_string(&mut data.as_slice()).unwrap();
+        assert_eq!(decode_par(&encoded).unwrap(), data);
+        assert_eq!(decode_par(&encoded).unwrap(), decode_str(&encoded).unwrap());
+        assert_eq!(decode_par_sharded(&encoded).unwrap(), data);
+
+        for len in 0..12 {
+            let encoded = ::encode_to_string(&mut &data[..len]).unwrap();
+            assert_eq!(decode_par(&encoded).unwrap(), &data[..len]);
+        }
+    }
+
+    #[test]
+    fn test_decode_par_errors() {
+        let data = vec![0x5a; 50_000];
+        let mut encoded: Vec<char> = ::encode_to_string(&mut data.as_slice()).unwrap().chars().collect();
+        encoded[30_001] = '★';
+        encoded[35_002] = '☆';
+        let encoded: String = encoded.into_iter().collect();
+
+        let serial = decode_str(&encoded).unwrap_err();
+        let parallel = decode_par_sharded(&encoded).unwrap_err();
+        assert_eq!(parallel.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            parallel.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+            serial.get_ref().and_then(|e| e.downcast_ref::<DecodeError>())
+        );
+        assert_eq!(parallel.to_string(), "invalid char '★' (U+2605) at position 30001");
+
+        // A padding in a data slot of a later shard
+        let mut encoded: Vec<char> = ::encode_to_string(&mut data.as_slice()).unwrap().chars().collect();
+        encoded[30_000] = '☕';
+        let encoded: String = encoded.into_iter().collect();
+
+        let serial = decode_str(&encoded).unwrap_err();
+        let parallel = decode_par_sharded(&encoded).unwrap_err();
+        assert_eq!(parallel.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            parallel.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+            Some(&DecodeError::MisplacedPadding { c: '☕', position: 30_000 })
+        );
+        assert_eq!(
+            parallel.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+            serial.get_ref().and_then(|e| e.downcast_ref::<DecodeError>())
+        );
+
+        assert_eq!(decode_par("👶😲🇲").unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,235 @@
+//! Encoding of sparse data with leading and trailing zero bytes trimmed.
+//!
+//! The trimmed zero bytes are replaced by a small header recording their counts, so data which
+//! starts or ends with long runs of zeros takes much less space. Such output is not a plain
+//! Ecoji encoding of the original data, so it must be decoded with
+//! [`decode_trimmed`](fn.decode_trimmed.html).
+
+use std::io;
+
+use decode::decode_str;
+use encode::encode_append;
+use error::DecodeError;
+
+/// Appends `value` to the output as an unsigned LEB128 number.
+fn write_varint(mut value: u64, output: &mut Vec<u8>) {
+    while value >= 0x80 {
+        output.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+/// Reads an unsigned LEB128 number from the beginning of the input and advances the input past it.
+fn read_varint(input: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for (i, &b) in input.iter().enumerate().take(10) {
+        let bits = u64::from(b & 0x7f);
+        if i == 9 && bits > 1 {
+            return None;
+        }
+        value |= bits << (7 * i);
+        if b & 0x80 == 0 {
+            *input = &input[i + 1..];
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Trims leading and trailing zero bytes from the input and encodes the rest into the Ecoji
+/// format together with a header recording how many zero bytes were trimmed, returning the result
+/// as a new string.
+///
+/// The encoded bytes are the header followed by the trimmed data. The header consists of two
+/// numbers in the unsigned LEB128 format, i.e. seven bits per byte, least significant group first,
+/// with the high bit set in every byte except the last one: the number of leading zero bytes,
+/// then the number of trailing zero bytes. If the input consists of zero bytes only, all of them
+/// are counted as leading. For example, the input `[0, 0, 0, 1, 2, 0]` is stored as the bytes
+/// `[3, 1, 1, 2]`.
+///
+/// The output must be decoded with [`decode_trimmed`](fn.decode_trimmed.html).
+///
+/// # Examples
+///
+/// ```
+/// # fn test() -> ::std::io::Result<()> {
+/// let mut input = vec![0; 1000];
+/// input[500] = 42;
+///
+/// let output = ecoji::encode_trimmed(&input);
+/// assert_eq!(output.chars().count(), 4);
+/// assert_eq!(ecoji::decode_trimmed(&output)?, input);
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn encode_trimmed(input: &[u8]) -> String {
+    let start = input.iter().position(|&b| b != 0).unwrap_or(input.len());
+    let end = input.iter().rposition(|&b| b != 0).map_or(start, |i| i + 1);
+
+    let mut data = Vec::with_capacity(20 + end - start);
+    write_varint(start as u64, &mut data);
+    write_varint((input.len() - end) as u64, &mut data);
+    data.extend_from_slice(&input[start..end]);
+
+    let mut output = Vec::new();
+    encode_append(&data, &mut output);
+    // encoded output is guaranteed to be valid UTF-8
+    unsafe { String::from_utf8_unchecked(output) }
+}
+
+/// Decodes data encoded with [`encode_trimmed`](fn.encode_trimmed.html), restoring the trimmed
+/// leading and trailing zero bytes.
+///
+/// The restored data is allocated in memory at once, so a header of a few bytes may request
+/// a huge allocation. For data from untrusted sources, use
+/// [`decode_trimmed_limited`](fn.decode_trimmed_limited.html) instead.
+///
+/// In addition to the [`decode`](fn.decode.html) failure conditions, this function returns an
+/// error of the `InvalidData` kind wrapping [`DecodeError::MalformedHeader`](enum.DecodeError.html)
+/// if the decoded data does not start with a valid header, or if the restored data cannot be
+/// allocated.
+pub fn decode_trimmed(input: &str) -> io::Result<Vec<u8>> {
+    decode_trimmed_with(input, None)
+}
+
+/// Decodes data encoded with [`encode_trimmed`](fn.encode_trimmed.html), restoring the trimmed
+/// leading and trailing zero bytes, unless the restored data would be larger than `max_bytes`.
+///
+/// This function is meant for decoding untrusted input: the size of the restored data is checked
+/// against the limit before anything is allocated for it.
+///
+/// In addition to the [`decode_trimmed`](fn.decode_trimmed.html) failure conditions, this function
+/// also returns an error of the `InvalidData` kind wrapping
+/// [`DecodeError::OutputTooLarge`](enum.DecodeError.html) if the limit is exceeded.
+///
+/// # Examples
+///
+/// ```
+/// use ecoji::DecodeError;
+///
+/// # fn test() -> ::std::io::Result<()> {
+/// let output = ecoji::encode_trimmed(&[0; 1000]);
+///
+/// assert_eq!(ecoji::decode_trimmed_limited(&output, 1000)?, vec![0; 1000]);
+///
+/// let error = ecoji::decode_trimmed_limited(&output, 999).unwrap_err();
+/// assert_eq!(
+///     error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+///     Some(&DecodeError::OutputTooLarge { limit: 999 })
+/// );
+/// #  Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+pub fn decode_trimmed_limited(input: &str, max_bytes: usize) -> io::Result<Vec<u8>> {
+    decode_trimmed_with(input, Some(max_bytes))
+}
+
+fn decode_trimmed_with(input: &str, max_bytes: Option<usize>) -> io::Result<Vec<u8>> {
+    let decoded = decode_str(input)?;
+    let mut data = decoded.as_slice();
+
+    let leading = read_varint(&mut data).ok_or_else(|| DecodeError::MalformedHeader.into_io())?;
+    let trailing = read_varint(&mut data).ok_or_else(|| DecodeError::MalformedHeader.into_io())?;
+    // No allocation can be larger than isize::MAX bytes
+    let len = leading.checked_add(trailing)
+        .and_then(|zeros| zeros.checked_add(data.len() as u64))
+        .filter(|&len| len <= isize::MAX as u64)
+        .ok_or_else(|| DecodeError::MalformedHeader.into_io())?;
+
+    if let Some(limit) = max_bytes {
+        if len > limit as u64 {
+            return Err(DecodeError::OutputTooLarge { limit }.into_io());
+        }
+    }
+
+    let mut output = Vec::new();
+    output.try_reserve_exact(len as usize).map_err(|_| DecodeError::MalformedHeader.into_io())?;
+    output.resize(leading as usize, 0);
+    output.extend_from_slice(data);
+    output.resize(len as usize, 0);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varint() {
+        for &value in &[0, 1, 127, 128, 300, 16_383, 16_384, u64::from(u32::MAX), u64::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(value, &mut bytes);
+            let mut input = &bytes[..];
+            assert_eq!(read_varint(&mut input), Some(value));
+            assert!(input.is_empty());
+        }
+
+        assert_eq!(read_varint(&mut &[0x80, 0x80][..]), None);
+        assert_eq!(read_varint(&mut &[0xff; 9].iter().chain(&[0x02]).cloned().collect::<Vec<_>>()[..]), None);
+    }
+
+    #[test]
+    fn test_header_format() {
+        let encoded = encode_trimmed(&[0, 0, 0, 1, 2, 0]);
+        assert_eq!(decode_str(&encoded).unwrap(), [3, 1, 1, 2]);
+
+        let encoded = encode_trimmed(&[0; 200]);
+        assert_eq!(decode_str(&encoded).unwrap(), [0xc8, 0x01, 0]);
+    }
+
+    #[test]
+    fn test_trimmed_round_trip() {
+        let inputs: [&[u8]; 7] = [b"", b"\0", b"\0\0\0", b"abc", b"\0\0abc", b"abc\0", b"\0a\0\0b\0"];
+        for input in inputs.iter() {
+            assert_eq!(decode_trimmed(&encode_trimmed(input)).unwrap(), *input);
+        }
+
+        let mut sparse = vec![0; 4096];
+        sparse[1000..1010].copy_from_slice(b"input data");
+        assert_eq!(decode_trimmed(&encode_trimmed(&sparse)).unwrap(), sparse);
+    }
+
+    #[test]
+    fn test_huge_header() {
+        // 2^62 leading zeros
+        let mut header = Vec::new();
+        write_varint(1 << 62, &mut header);
+        write_varint(0, &mut header);
+        header.push(1);
+        let encoded = ::encode_to_string(&mut &header[..]).unwrap();
+
+        let error = decode_trimmed(&encoded).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+            Some(&DecodeError::MalformedHeader)
+        );
+
+        let error = decode_trimmed_limited(&encoded, 1 << 20).unwrap_err();
+        assert_eq!(
+            error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+            Some(&DecodeError::OutputTooLarge { limit: 1 << 20 })
+        );
+
+        let mut sparse = vec![0; 4096];
+        sparse[100] = 1;
+        let encoded = encode_trimmed(&sparse);
+        assert_eq!(decode_trimmed_limited(&encoded, 4096).unwrap(), sparse);
+        assert!(decode_trimmed_limited(&encoded, 4095).is_err());
+    }
+
+    #[test]
+    fn test_malformed_header() {
+        for header in &[&[][..], &[3], &[0x80], &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01, 1]] {
+            let encoded = ::encode_to_string(&mut &header[..]).unwrap();
+            let error = decode_trimmed(&encoded).unwrap_err();
+            assert_eq!(
+                error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()),
+                Some(&DecodeError::MalformedHeader)
+            );
+        }
+    }
+}